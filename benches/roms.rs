@@ -0,0 +1,103 @@
+//  Instructions per second on small ROMs that each stress one part of the
+//  interpreter, in every execution mode. Run with `cargo bench --bench roms`;
+//  criterion keeps the previous results and reports the change against them.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use chip_8::Machine;
+
+//  Instructions executed per measured iteration
+const INSTRUCTIONS: usize = 10_000;
+
+//  ALU instructions in a tight loop
+const ARITHMETIC: &[u8] = &[
+    0x60, 0x01,     //  Set R0 to 1
+    0x71, 0x01,     //  0x202: increment R1
+    0x82, 0x14,     //  R2 += R1
+    0x83, 0x25,     //  R3 -= R2
+    0x84, 0x06,     //  R4 >>= 1
+    0x85, 0x1E,     //  R5 <<= 1
+    0x86, 0x13,     //  R6 ^= R1
+    0x87, 0x12,     //  R7 &= R1
+    0x12, 0x02,     //  Loop
+];
+
+//  Draws a different font character at a different place every iteration
+const DRAWING: &[u8] = &[
+    0x63, 0x0F,     //  Set R3 to 0xF, the last character
+    0x84, 0x00,     //  0x202: copy R0 to R4
+    0x84, 0x32,     //  R4 &= R3, so it names a character
+    0xF4, 0x29,     //  Point at the character for R4
+    0xD1, 0x25,     //  Draw it at (R1, R2)
+    0x71, 0x03,     //  Move right
+    0x72, 0x05,     //  Move down
+    0x70, 0x01,     //  Next character
+    0x12, 0x02,     //  Loop
+];
+
+//  Stores and loads all sixteen registers
+const MEMORY_TRAFFIC: &[u8] = &[
+    0xA3, 0x00,     //  Point at 0x300
+    0xFF, 0x55,     //  Store R0 to RF
+    0xA3, 0x00,     //  Point at 0x300 again, whatever the quirks did to I
+    0xFF, 0x65,     //  Load R0 to RF
+    0x70, 0x01,     //  Change what is stored next time
+    0x12, 0x00,     //  Loop
+];
+
+//  Arithmetic, BCD stores and skips, with a sprite draw every 256 loops
+const BUSY_LOOP: &[u8] = &[
+    0x60, 0x00,     //  Set R0 to 0
+    0xA3, 0x00,     //  Point at 0x300
+    0x70, 0x01,     //  0x204: increment R0
+    0x81, 0x04,     //  R1 += R0
+    0x82, 0x13,     //  R2 ^= R1
+    0xF0, 0x33,     //  Store R0 as BCD at 0x300
+    0x30, 0x00,     //  Skip the draw unless R0 wrapped to 0
+    0x12, 0x12,     //  Jump over the draw
+    0xD1, 0x23,     //  Draw three rows at (R1, R2)
+    0x12, 0x04,     //  0x212: loop
+];
+
+const MODES: [(&str, bool, bool); 3] = [
+    ("plain", false, false),
+    ("decode cache", true, false),
+    ("basic blocks", false, true),
+];
+
+fn machine(rom: &[u8], decode_cache: bool, basic_blocks: bool) -> Machine {
+    let mut machine = Machine::new();
+    machine.set_cycles_per_frame(INSTRUCTIONS);
+    machine.set_decode_cache(decode_cache);
+    machine.set_basic_blocks(basic_blocks);
+    machine.load_rom(rom).unwrap();
+    machine
+}
+
+fn bench_rom(c: &mut Criterion, name: &str, rom: &[u8]) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+    for &(mode, decode_cache, basic_blocks) in MODES.iter() {
+        let mut machine = machine(rom, decode_cache, basic_blocks);
+        //  The ROMs loop forever, so every frame is a full INSTRUCTIONS long
+        group.bench_function(BenchmarkId::from_parameter(mode), |b| b.iter(|| machine.run_frame()));
+    }
+    group.finish();
+}
+
+fn arithmetic(c: &mut Criterion) {
+    bench_rom(c, "arithmetic", ARITHMETIC);
+}
+
+fn drawing(c: &mut Criterion) {
+    bench_rom(c, "drawing", DRAWING);
+}
+
+fn memory_traffic(c: &mut Criterion) {
+    bench_rom(c, "memory traffic", MEMORY_TRAFFIC);
+}
+
+fn busy_loop(c: &mut Criterion) {
+    bench_rom(c, "busy loop", BUSY_LOOP);
+}
+
+criterion_group!(benches, arithmetic, drawing, memory_traffic, busy_loop);
+criterion_main!(benches);
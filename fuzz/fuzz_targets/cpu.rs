@@ -0,0 +1,71 @@
+//  Runs arbitrary programs with arbitrary quirks and key presses. The
+//  interpreter must report bad programs through CPU::error, never panic.
+//  Run with `cargo +nightly fuzz run cpu` from the repository root.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use chip_8::cpu::cpu::CPU;
+use chip_8::cpu::machine_code::MachineCodePolicy;
+use chip_8::cpu::memory_map::MemoryMap;
+use chip_8::cpu::quirks::Quirks;
+
+const FRAMES: usize = 60;
+
+fuzz_target!(|data: &[u8]| {
+    //  Two bytes of quirks, one of execution settings, one key event per
+    //  frame and the rest is the ROM
+    if data.len() < 3 + FRAMES {
+        return;
+    }
+    let (settings, rest) = data.split_at(3);
+    let (keys, rom) = rest.split_at(FRAMES);
+    let bit = |byte: u8, index: u8| byte >> index & 1 != 0;
+    let quirks = Quirks {
+        shift: bit(settings[0], 0),
+        memory_increment_by_x: bit(settings[0], 1),
+        memory_leave_i_unchanged: bit(settings[0], 2),
+        wrap: bit(settings[0], 3),
+        jump: bit(settings[0], 4),
+        vblank: bit(settings[0], 5),
+        logic: bit(settings[0], 6),
+        pointer_overflow: bit(settings[0], 7),
+        address_mask: match bit(settings[1], 0) {
+            true => 0xFFFF,
+            false => 0x0FFF,
+        },
+    };
+    let mut cpu = match CPU::builder()
+        .rom(rom)
+        .quirks(quirks)
+        .cycles_per_frame(1 + (settings[2] >> 2) as usize)
+        .decode_cache(bit(settings[2], 0))
+        .basic_blocks(bit(settings[2], 1))
+        .strict(bit(settings[1], 1))
+        .memory_map(match bit(settings[1], 2) {
+            true => MemoryMap::cosmac_vip(),
+            false => MemoryMap::standard(),
+        })
+        .machine_code_policy(match bit(settings[1], 3) {
+            true => MachineCodePolicy::Ignore,
+            false => MachineCodePolicy::Error,
+        })
+        .random_seed(0)
+        .build() {
+        Ok(cpu) => cpu,
+        //  Too long to load
+        Err(_) => return,
+    };
+
+    for &key in keys {
+        cpu.set_key(key & 0x0F, bit(key, 4));
+        cpu.run_frame();
+        assert!(cpu.stack_pointer() <= 16);
+        match cpu.error() {
+            Some(_) => assert!(cpu.is_halted()),
+            None => assert!((cpu.program_counter() as usize) + 1 < cpu.memory().len()),
+        }
+        assert!(cpu.pointer_register() <= quirks.address_mask);
+        if cpu.is_halted() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,95 @@
+use std::rc::Rc;
+use crate::cpu::instruction::{decode, Instruction};
+
+//  Longer runs of straight-line code are split into several blocks
+const MAX_BLOCK_LENGTH: usize = 64;
+
+//  A basic block: instructions that always execute one after the other,
+//  decoded once and run without fetching. Only the last one may jump,
+//  skip, wait, draw, write memory or halt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+}
+
+//  Blocks by start address, and which bytes of memory they were decoded
+//  from, so that writes to code can throw them away
+#[derive(Clone, Debug)]
+pub struct BlockCache {
+    blocks: Vec<Option<Rc<Block>>>,
+    code: Vec<bool>,
+    //  How often writes to code flushed the cache
+    flushes: u64,
+}
+
+impl BlockCache {
+    pub fn new(memory_size: usize) -> BlockCache {
+        BlockCache {
+            blocks: vec![None; memory_size],
+            code: vec![false; memory_size],
+            flushes: 0,
+        }
+    }
+
+    //  The block starting at `pc`, translating it on first use. None when
+    //  the first instruction cannot be decoded.
+    pub fn block_at(&mut self, pc: usize, memory: &[u8]) -> Option<Rc<Block>> {
+        if let Some(block) = &self.blocks[pc] {
+            return Some(Rc::clone(block));
+        }
+        let block = Rc::new(translate(pc, memory)?);
+        for address in pc..pc + 2 * block.instructions.len() {
+            self.code[address] = true;
+        }
+        self.blocks[pc] = Some(Rc::clone(&block));
+        Some(block)
+    }
+
+    //  Called when memory is written; self-modifying code is rare enough
+    //  that dropping every block is simpler than tracking which ones overlap
+    pub fn memory_written(&mut self, address: usize, length: usize) {
+        let end = (address + length).min(self.code.len());
+        if self.code[address.min(end)..end].iter().any(|&code| code) {
+            self.blocks.iter_mut().for_each(|block| *block = None);
+            self.code.iter_mut().for_each(|code| *code = false);
+            self.flushes += 1;
+        }
+    }
+
+    pub fn flushes(&self) -> u64 {
+        self.flushes
+    }
+}
+
+fn translate(start: usize, memory: &[u8]) -> Option<Block> {
+    let mut instructions = Vec::new();
+    let mut pc = start;
+    while pc + 1 < memory.len() && instructions.len() < MAX_BLOCK_LENGTH {
+        let op_code = (memory[pc] as u16) << 8 | memory[pc + 1] as u16;
+        let instruction = match decode(op_code) {
+            Ok(instruction) => instruction,
+            //  Left for the interpreter to report when it gets there
+            Err(_) => break,
+        };
+        instructions.push(instruction);
+        pc += 2;
+        if ends_block(instruction) {
+            break;
+        }
+    }
+    match instructions.is_empty() {
+        true => None,
+        false => Some(Block { start, instructions }),
+    }
+}
+
+fn ends_block(instruction: Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(instruction,
+        Halt | System { .. } | Return | Jump { .. } | Call { .. } | JumpWithOffset { .. }
+        | SkipIfEqual { .. } | SkipIfNotEqual { .. } | SkipIfRegistersEqual { .. }
+        | SkipIfRegistersNotEqual { .. } | SkipIfKey { .. } | SkipIfNotKey { .. }
+        | WaitForKey { .. } | Draw { .. } | StoreBcd { .. } | StoreRegisters { .. })
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+use crate::cpu::blocks::BlockCache;
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::instruction::Instruction;
+
+//  Runs `frames` frames with and without basic blocks and checks that both
+//  machines end up in the same state
+#[cfg(test)]
+fn assert_same_as_stepping(program: &[u8], cycles_per_frame: usize, frames: usize) -> CPU {
+    let run = |basic_blocks| {
+        let mut cpu = CPU::builder().rom(program).cycles_per_frame(cycles_per_frame).build().unwrap();
+        cpu.set_basic_blocks(basic_blocks);
+        for _ in 0..frames {
+            cpu.run_frame();
+        }
+        cpu
+    };
+    let (stepped, blocks) = (run(false), run(true));
+    assert_eq!(blocks.registers(), stepped.registers());
+    assert_eq!(blocks.program_counter(), stepped.program_counter());
+    assert_eq!(blocks.pointer_register(), stepped.pointer_register());
+    assert_eq!(blocks.stack(), stepped.stack());
+    assert_eq!(blocks.delay_timer(), stepped.delay_timer());
+    assert_eq!(blocks.memory(), stepped.memory());
+    assert_eq!(blocks.framebuffer(), stepped.framebuffer());
+    assert_eq!(blocks.is_halted(), stepped.is_halted());
+    blocks
+}
+
+#[test]
+fn blocks_end_at_control_flow(){
+    let mut memory = vec![0u8; 0x1000];
+    memory[0x200..0x20A].copy_from_slice(&[
+        0x60, 0x01,     //  Set R0 to 1
+        0x70, 0x02,     //  Add 2
+        0x30, 0x03,     //  Skip if R0 is 3
+        0x61, 0x05,     //  Next block
+        0x12, 0x00,     //  Loop
+    ]);
+    let mut blocks = BlockCache::new(memory.len());
+    let block = blocks.block_at(0x200, &memory).unwrap();
+    assert_eq!(block.instructions, vec![
+        Instruction::Load { x: 0, value: 1 },
+        Instruction::AddConstant { x: 0, value: 2 },
+        Instruction::SkipIfEqual { x: 0, value: 3 },
+    ]);
+    assert_eq!(blocks.block_at(0x206, &memory).unwrap().instructions.len(), 2);
+    memory[0x300] = 0xFF;
+    assert!(blocks.block_at(0x300, &memory).is_none());
+}
+
+#[test]
+fn blocks_match_stepping_across_frame_boundaries(){
+    //  A delay loop that reads the timer, which only ticks between frames
+    let cpu = assert_same_as_stepping(&[
+        0x60, 0x3C,     //  Set R0 to 60
+        0xF0, 0x15,     //  Start the delay timer
+        0x71, 0x01,     //  0x204: count iterations in R1
+        0x72, 0x03,     //  Busy work in R2
+        0x82, 0x14,
+        0xF3, 0x07,     //  Read the timer
+        0x33, 0x00,     //  Until it reaches 0
+        0x12, 0x04,
+        0x00, 0x00,     //  Halt
+    ], 7, 70);
+    assert!(cpu.is_halted());
+}
+
+#[test]
+fn blocks_match_stepping_with_draws_and_memory_traffic(){
+    assert_same_as_stepping(&[
+        0xA3, 0x00,     //  Point at 0x300
+        0x70, 0x07,     //  0x202: R0 += 7
+        0x81, 0x04,     //  R1 += R0
+        0xF1, 0x55,     //  Store R0 and R1 at 0x300
+        0xF1, 0x65,     //  Load them back
+        0xF1, 0x33,     //  Store R1 as BCD
+        0xD0, 0x13,     //  Draw three rows of it at (R0, R1)
+        0x12, 0x02,     //  Loop
+    ], 11, 50);
+}
+
+#[test]
+fn writes_to_code_flush_the_blocks(){
+    let program = [
+        0x62, 0x00,     //  Set R2 to 0, the pass counter
+        0x63, 0x07,     //  0x202: set R3 to 7, rewritten to set it to 9
+        0x72, 0x01,     //  Increment R2
+        0x32, 0x02,     //  Skip the jump on the second pass
+        0x12, 0x0E,     //  Jump to 0x20E
+        0x00, 0x00,     //  Halt
+        0x00, 0x00,
+        0x60, 0x63,     //  0x20E: R0 and R1 hold the opcode 6309
+        0x61, 0x09,
+        0xA2, 0x02,     //  Point at 0x202
+        0xF1, 0x55,     //  Overwrite the instruction there
+        0x12, 0x02,     //  Jump back to it
+    ];
+    let cpu = assert_same_as_stepping(&program, 1000, 1);
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.registers()[3], 9);
+    assert_eq!(cpu.block_cache_flushes(), 1);
+}
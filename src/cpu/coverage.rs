@@ -0,0 +1,157 @@
+use std::fmt::Write;
+use crate::cpu::disassembler;
+
+pub const EXECUTED: u8 = 1;
+pub const READ: u8 = 2;
+pub const WRITTEN: u8 = 4;
+
+//  Where programs are loaded
+const ROM_START: usize = 0x200;
+
+//  Records how every byte of memory was used: fetched as part of an
+//  opcode, read as data (sprites, FX65) or written (FX55, FX33).
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    flags: Vec<u8>,
+    rom_length: usize,
+    //  What was first fetched at each address, for the disassembly
+    opcodes: Vec<Option<u16>>,
+}
+
+impl Coverage {
+    pub fn new(rom_length: usize) -> Coverage {
+        Coverage {
+            flags: vec![0; 0x1000],
+            rom_length: rom_length.min(0x1000 - ROM_START),
+            opcodes: vec![None; 0x1000],
+        }
+    }
+
+    //  Called before `op_code`, fetched from `pc`, is executed with the
+    //  pointer register at `i`
+    pub fn record(&mut self, pc: u16, op_code: u16, i: u16) {
+        let pc = pc as usize;
+        self.mark(pc, 2, EXECUTED);
+        self.opcodes[pc & 0x0FFF].get_or_insert(op_code);
+
+        let i = i as usize;
+        let x = ((op_code & 0x0F00) >> 8) as usize;
+        match op_code & 0xF0FF {
+            0xF033 => self.mark(i, 3, WRITTEN),
+            0xF055 => self.mark(i, x + 1, WRITTEN),
+            0xF065 => self.mark(i, x + 1, READ),
+            _ if op_code & 0xF000 == 0xD000 => self.mark(i, (op_code & 0x000F) as usize, READ),
+            _ => {}
+        }
+    }
+
+    fn mark(&mut self, address: usize, length: usize, flag: u8) {
+        for offset in 0..length {
+            self.flags[(address + offset) & 0x0FFF] |= flag;
+        }
+    }
+
+    pub fn flags_at(&self, address: u16) -> u8 {
+        self.flags[address as usize & 0x0FFF]
+    }
+
+    fn rom_addresses(&self) -> std::ops::Range<usize> {
+        ROM_START..ROM_START + self.rom_length
+    }
+
+    //  Bytes of the ROM that were executed, read or written at least once
+    pub fn rom_bytes_used(&self) -> usize {
+        self.rom_addresses().filter(|&address| self.flags[address] != 0).count()
+    }
+
+    pub fn summary(&self) -> String {
+        let count = |flag: u8| self.rom_addresses().filter(|&address| self.flags[address] & flag != 0).count();
+        let percent = |bytes: usize| match self.rom_length {
+            0 => 0.0,
+            length => 100.0 * bytes as f64 / length as f64,
+        };
+        let (executed, read, written, used) = (count(EXECUTED), count(READ), count(WRITTEN), self.rom_bytes_used());
+        format!("{} of {} ROM bytes used ({:.1}%): {} executed ({:.1}%), {} read ({:.1}%), {} written ({:.1}%)",
+                used, self.rom_length, percent(used), executed, percent(executed),
+                read, percent(read), written, percent(written))
+    }
+
+    //  A disassembly of the ROM with a column telling how each line was used:
+    //  X executed, R read, W written, - never touched. Executed addresses are
+    //  shown as instructions, everything else as data bytes.
+    pub fn annotated_disassembly(&self, memory: &[u8]) -> String {
+        let mut listing = format!("; {}\n", self.summary());
+        let mut address = ROM_START;
+        let end = ROM_START + self.rom_length;
+        while address < end {
+            match self.opcodes[address] {
+                Some(op_code) => {
+                    let flags = self.flags[address] | self.flags[(address + 1) & 0x0FFF];
+                    writeln!(listing, "{:<3}  {:03X}  {:04X}  {}",
+                             markers(flags), address, op_code, disassembler::disassemble(op_code)).unwrap();
+                    address += 2;
+                }
+                None => {
+                    writeln!(listing, "{:<3}  {:03X}  {:02X}    DB 0x{:02X}",
+                             markers(self.flags[address]), address, memory[address], memory[address]).unwrap();
+                    address += 1;
+                }
+            }
+        }
+        listing
+    }
+
+    //  A page with one cell per ROM byte, sixteen to a row, coloured by use
+    pub fn html_map(&self, memory: &[u8]) -> String {
+        let mut html = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>ROM coverage</title>\n<style>\n",
+            "body { font-family: monospace; }\n",
+            "td { padding: 2px 4px; text-align: center; }\n",
+            ".unused { background: #eeeeee; color: #999999; }\n",
+            ".x { background: #7fd17f; }\n",
+            ".r { background: #7fb2f0; }\n",
+            ".w { background: #f0a07f; }\n",
+            ".rw { background: #c99fe6; }\n",
+            "</style>\n</head>\n<body>\n",
+        ));
+        writeln!(html, "<p>{}</p>", self.summary()).unwrap();
+        html.push_str(concat!(
+            "<p><span class=\"x\">executed</span> <span class=\"r\">read</span> ",
+            "<span class=\"w\">written</span> <span class=\"rw\">read and written</span> ",
+            "<span class=\"unused\">never touched</span></p>\n<table>\n",
+        ));
+        let end = ROM_START + self.rom_length;
+        for row in (ROM_START..end).step_by(16) {
+            write!(html, "<tr><th>{:03X}</th>", row).unwrap();
+            let last = (row + 16).min(end);
+            for (address, byte) in memory.iter().enumerate().take(last).skip(row) {
+                let flags = self.flags[address];
+                let class = match (flags & EXECUTED != 0, flags & READ != 0, flags & WRITTEN != 0) {
+                    (true, _, _) => "x",
+                    (false, true, true) => "rw",
+                    (false, true, false) => "r",
+                    (false, false, true) => "w",
+                    (false, false, false) => "unused",
+                };
+                let title = match self.opcodes[address] {
+                    Some(op_code) => format!("{:03X}: {}", address, disassembler::disassemble(op_code)),
+                    None => format!("{:03X}: {}", address, markers(flags)),
+                };
+                write!(html, "<td class=\"{}\" title=\"{}\">{:02X}</td>", class, title, byte).unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn markers(flags: u8) -> String {
+    if flags == 0 {
+        return "-".to_string();
+    }
+    [(EXECUTED, 'X'), (READ, 'R'), (WRITTEN, 'W')].iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|&(_, marker)| marker)
+        .collect()
+}
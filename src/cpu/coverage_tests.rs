@@ -0,0 +1,62 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::coverage::{Coverage, EXECUTED, READ, WRITTEN};
+
+#[cfg(test)]
+fn covered_run(program: Vec<u8>) -> (CPU, Coverage) {
+    let mut cpu = CPU::new_with_memory(program.clone());
+    cpu.set_coverage(Coverage::new(program.len()));
+    cpu.run_headless().unwrap();
+    let coverage = cpu.take_coverage().unwrap();
+    (cpu, coverage)
+}
+
+#[cfg(test)]
+fn program() -> Vec<u8> {
+    vec![
+        0xA2, 0x0C,     //  Point at the sprite at 0x20C
+        0xD0, 0x02,     //  Draw two rows of it
+        0xA2, 0x0E,     //  Point at the scratch bytes at 0x20E
+        0xF1, 0x55,     //  Store R0 and R1
+        0xF0, 0x65,     //  Load R0 back
+        0x00, 0x00,     //  Halt
+        0xFF, 0x81,     //  Sprite
+        0x00, 0x00,     //  Scratch
+        0x00, 0x00,     //  Never used
+    ]
+}
+
+#[test]
+fn classifies_rom_bytes(){
+    let (_, coverage) = covered_run(program());
+    assert_eq!(coverage.flags_at(0x200), EXECUTED);
+    assert_eq!(coverage.flags_at(0x20B), EXECUTED);
+    assert_eq!(coverage.flags_at(0x20C), READ);
+    assert_eq!(coverage.flags_at(0x20D), READ);
+    assert_eq!(coverage.flags_at(0x20E), READ | WRITTEN);
+    assert_eq!(coverage.flags_at(0x20F), WRITTEN);
+    assert_eq!(coverage.flags_at(0x210), 0);
+    assert_eq!(coverage.rom_bytes_used(), 16);
+}
+
+#[test]
+fn annotates_the_disassembly(){
+    let (cpu, coverage) = covered_run(program());
+    let listing = coverage.annotated_disassembly(cpu.memory());
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines[0], "; 16 of 18 ROM bytes used (88.9%): 12 executed (66.7%), 3 read (16.7%), 2 written (11.1%)");
+    assert_eq!(lines[2], "X    202  D002  DRW V0, V0, 2");
+    assert_eq!(lines[7], "R    20C  FF    DB 0xFF");
+    assert_eq!(lines[9], "RW   20E  00    DB 0x00");
+    assert_eq!(lines[11], "-    210  00    DB 0x00");
+}
+
+#[test]
+fn maps_the_rom_as_html(){
+    let (cpu, coverage) = covered_run(program());
+    let html = coverage.html_map(cpu.memory());
+    assert!(html.contains("<tr><th>200</th><td class=\"x\" title=\"200: LD I, 0x20C\">A2</td>"));
+    assert!(html.contains("<td class=\"rw\" title=\"20E: RW\">00</td>"));
+    assert!(html.contains("<tr><th>210</th><td class=\"unused\" title=\"210: -\">00</td><td"));
+}
@@ -1,110 +1,123 @@
-#![allow(dead_code)]
-
-use std::ops::{ShlAssign, ShrAssign, BitAnd, Div, Rem};
-use rand::Rng;
-use ggez::{conf::{self, WindowMode}, event::{self, EventHandler}, graphics::{self}, ContextBuilder, GameResult, Context};
-use ggez::graphics::{Color, DrawParam, FilterMode};
-use ggez::event::winit_event::{Event, WindowEvent, KeyboardInput, ElementState};
-use ggez::input::keyboard;
-use ggez::conf::WindowSetup;
-use ggez::event::{EventsLoop, KeyCode};
-
+use std::io;
+use std::ops::BitAnd;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::cpu::quirks::Quirks;
+use crate::cpu::timing::{self, TimingMode};
+use crate::cpu::disassembler;
+use crate::cpu::instruction::{decode, DecodeError, Instruction};
+use crate::cpu::machine_code::MachineCodePolicy;
+use crate::cpu::memory_map::MemoryMap;
+use crate::cpu::blocks::BlockCache;
+use crate::cpu::coverage::Coverage;
+use crate::cpu::display::Framebuffer;
+use crate::cpu::error::ExecutionError;
+use crate::cpu::font::Font;
+use crate::cpu::profiler::Profiler;
+use crate::cpu::stack::{Stack, StackStorage};
+use crate::cpu::trace::{Columns, TraceRecord, Tracer};
+
+//  The interpreter's own jump from 0x000 to the program, which is not
+//  part of the program and is left out of traces
+const BOOT_JUMP: u16 = 0x1200;
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     registers: [u8; 16],
     program_counter: usize,
     memory: [u8; 0x1000],
-    stack: [u16; 16],
-    stack_pointer: usize,
+    stack: Stack,
     pointer_register: u16,
-
-    display: VirtualDisplay<bool>,
-    waiting_for_input: bool,
-    input_register_index: Option<usize>,
-    keycode_map: [KeyCode; 16],
+    font: Font,
+    memory_map: MemoryMap,
+    //  Whether writes and jumps that break the memory map are errors
+    strict: bool,
+
+    display: Framebuffer,
+    display_dirty: bool,
+    key_wait: Option<KeyWait>,
+    keypad: [bool; 16],
     delay_timer: u8,
+    sound_timer: u8,
+    halted: bool,
+    //  For CXKK; seeded from entropy unless a test asks for a fixed seed
+    rng: StdRng,
+    //  What stopped the program, when it did not halt by itself
+    error: Option<ExecutionError>,
+
+    quirks: Quirks,
+    machine_code: MachineCodePolicy,
+    timing: TimingMode,
+    cycles_per_frame: usize,
+    frame_cycles: usize,
+    frame_count: u64,
+    waiting_for_vblank: bool,
+
+    cycles: u64,
+    //  Instructions already decoded, by address, when the cache is enabled
+    decoded: Option<Vec<Option<Instruction>>>,
+    blocks: Option<BlockCache>,
+    tracer: Option<Tracer>,
+    //  Why tracing stopped, when writing the trace failed
+    trace_error: Option<io::Error>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
 }
 
-#[derive(Clone)]
-struct VirtualDisplay<T> {
-    data: [T; 2048],
-    //  size is 64*32
-    dirty_bit: bool,
-}
-
-impl EventHandler for VirtualDisplay<bool> {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        Ok(())
-    }
-
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let image_bytes = self.data.iter()
-            .map(|bit| match bit {
-                true => vec![255u8, 255u8, 255u8, 255u8],
-                false => vec![0u8, 0u8, 0u8, 255u8]
-            })
-            .flatten()
-            .collect::<Vec<u8>>();
-
-        let mut image =
-            ggez::graphics::Image::from_rgba8(ctx, 64, 32, &image_bytes)?;
-        let draw_params = DrawParam::default().scale([10.0, 10.0]);
-        image.set_filter(FilterMode::Nearest);
-        ggez::graphics::draw(ctx, &image, draw_params)?;
-        self.dirty_bit = false;
-        graphics::present(ctx)?;
-        Ok(())
-    }
+//  Progress of an FX0A instruction. As on the COSMAC VIP, the instruction
+//  only completes once a key has been pressed and then released; until then
+//  it is executed again on every cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum KeyWait {
+    ForPress,
+    ForRelease(u8),
 }
 
-impl CPU {
-    pub fn default() -> CPU {
+impl Default for CPU {
+    fn default() -> CPU {
         let mut memory = [0u8; 0x1000];
-        let init_memory: [u8; 82] =
-            [0x12, 0x00,                     //  Jump to 0x200
-                0xF0, 0x90, 0x90, 0x90, 0xF0,   //  Font set starts here
-                0x20, 0x60, 0x20, 0x20, 0x70,
-                0xF0, 0x10, 0xF0, 0x80, 0xF0,
-                0xF0, 0x10, 0xF0, 0x10, 0xF0,
-                0x90, 0x90, 0xF0, 0x10, 0x10,
-                0xF0, 0x80, 0xF0, 0x10, 0xF0,
-                0xF0, 0x80, 0xF0, 0x90, 0xF0,
-                0xF0, 0x10, 0x20, 0x40, 0x40,
-                0xF0, 0x90, 0xF0, 0x90, 0xF0,
-                0xF0, 0x90, 0xF0, 0x10, 0xF0,
-                0xF0, 0x90, 0xF0, 0x90, 0x90,
-                0xE0, 0x90, 0xE0, 0x90, 0xE0,
-                0xF0, 0x80, 0x80, 0x80, 0xF0,
-                0xE0, 0x90, 0x90, 0x90, 0xE0,
-                0xF0, 0x80, 0xF0, 0x80, 0xF0,
-                0xF0, 0x80, 0xF0, 0x80, 0x80
-            ];
-        memory[0..82].copy_from_slice(&init_memory);
+        memory[0..2].copy_from_slice(&BOOT_JUMP.to_be_bytes());
+        let font = Font::default();
+        memory[font.range()].copy_from_slice(font.glyphs());
         CPU {
             registers: [0u8; 16],
             program_counter: 0,
             memory,
-            stack: [0u16; 16],
-            stack_pointer: 0,
+            stack: Stack::default(),
             pointer_register: 0,
-            display: VirtualDisplay {
-                data: [false; 2048],
-                dirty_bit: false,
-            },
-            waiting_for_input: false,
-            input_register_index: None,
-            keycode_map: [KeyCode::X,
-                KeyCode::Key1, KeyCode::Key2, KeyCode::Key3,
-                KeyCode::Q, KeyCode::W, KeyCode::E,
-                KeyCode::A, KeyCode::S, KeyCode::D,
-                KeyCode::Z, KeyCode::C, KeyCode::Key4,
-                KeyCode::R, KeyCode::F, KeyCode::V],
+            font,
+            memory_map: MemoryMap::default(),
+            strict: false,
+            display: Framebuffer::new(64, 32),
+            display_dirty: false,
+            key_wait: None,
+            keypad: [false; 16],
             delay_timer: 0,
+            sound_timer: 0,
+            halted: false,
+            rng: StdRng::from_entropy(),
+            error: None,
+            quirks: Quirks::default(),
+            machine_code: MachineCodePolicy::default(),
+            timing: TimingMode::Instructions,
+            cycles_per_frame: 1,
+            frame_cycles: 0,
+            frame_count: 0,
+            waiting_for_vblank: false,
+            cycles: 0,
+            decoded: None,
+            blocks: None,
+            tracer: None,
+            trace_error: None,
+            profiler: None,
+            coverage: None,
         }
     }
+}
 
+impl CPU {
     pub fn new(registers: [u8; 16], memory_init: Vec<u8>) -> CPU {
-        let mut cpu = CPU::default();
-        cpu.registers = registers;
+        let mut cpu = CPU { registers, ..CPU::default() };
         cpu.memory[0x200..0x200 + memory_init.len()].copy_from_slice(memory_init.as_slice());
         cpu
     }
@@ -116,215 +129,597 @@ impl CPU {
         cpu
     }
 
-    pub fn run(&mut self) {
-        let (mut ctx, mut event_loop) = CPU::create_context_and_loop();
+    //  Runs until the program halts; input can only come from set_key, so
+    //  use step for programs that wait for keys.
+    pub fn run_headless(&mut self) -> Result<(), ExecutionError> {
+        while !self.halted {
+            self.advance();
+        }
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
 
-        graphics::clear(&mut ctx, Color::from_rgb(0, 0, 0));
+    //  Executes a single instruction. Timers tick at the end of every frame,
+    //  which lasts `cycles_per_frame` instructions or, with COSMAC VIP timing,
+    //  as many machine cycles as the VIP had available for the interpreter.
+    //  Does nothing once the program has halted or failed.
+    pub fn step(&mut self) {
+        if self.halted || self.error.is_some() {
+            return;
+        }
+        let (pc, op_code) = (self.program_counter, self.read_opcode());
+        let cost = match self.timing {
+            TimingMode::Instructions => 1,
+            TimingMode::CosmacVip => timing::vip_machine_cycles(op_code, &self.registers),
+        };
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(pc as u16, op_code, self.pointer_register);
+        }
+        let result = self.emulate_cycle();
+        let executed = result.is_ok();
+        if let Err(error) = result {
+            self.fail(error);
+        }
+        self.cycles += 1;
+        self.frame_cycles += cost;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc as u16, op_code, self.key_wait.is_some(), executed);
+        }
+        self.end_frame_if_due();
 
-        while ctx.continuing {
-            ctx.timer_context.tick();
-            event_loop.poll_events(|event| {
-                    ctx.process_event(&event);
-                    self.handle_event(&mut ctx, event);
-                }
-            );
-            
-            while ggez::timer::check_update_time(&mut ctx, 60) {
-                if !self.waiting_for_input {
-                    self.emulate_cycle(&mut ctx)
-                }
-            }
+        if self.tracer.is_some() && !(pc == 0 && op_code == BOOT_JUMP) {
+            self.trace(pc, op_code);
+        }
+    }
 
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
+    fn end_frame_if_due(&mut self) {
+        let budget = self.frame_budget();
+        if self.waiting_for_vblank {
+            self.frame_cycles = 0;
+            self.end_frame();
+        } else if self.frame_cycles >= budget {
+            //  An instruction straddling the frame boundary finishes in the next frame
+            self.frame_cycles -= budget;
+            self.end_frame();
+        }
+    }
+
+    //  Runs a basic block when it gives the same result as stepping through
+    //  it: tools that watch every instruction, VIP timing, which charges
+    //  every instruction differently, and strict mode, which checks every
+    //  fetch against the memory map, need single steps.
+    fn advance(&mut self) {
+        let blocks_apply = self.blocks.is_some() && self.timing == TimingMode::Instructions && !self.strict
+            && self.tracer.is_none() && self.profiler.is_none() && self.coverage.is_none();
+        match blocks_apply {
+            true => self.run_block(),
+            false => self.step(),
+        }
+    }
 
-            if self.display.dirty_bit {
-                self.display.draw(&mut ctx).unwrap();
+    fn run_block(&mut self) {
+        let start = self.program_counter;
+        let memory = &self.memory;
+        let block = match self.blocks.as_mut().and_then(|blocks| blocks.block_at(start, memory)) {
+            Some(block) => block,
+            None => return self.step(),
+        };
+        //  Stop at the end of the frame, where the timers tick
+        let remaining = self.frame_budget().saturating_sub(self.frame_cycles).max(1);
+        let mut count = block.instructions.len().min(remaining);
+        for (index, &instruction) in block.instructions[..count].iter().enumerate() {
+            self.program_counter = start + 2 * (index + 1);
+            if let Err(error) = self.execute(instruction) {
+                self.fail(error);
+                count = index + 1;
+                break;
             }
         }
+        self.cycles += count as u64;
+        self.frame_cycles += count;
+        self.end_frame_if_due();
     }
 
-    fn create_context_and_loop() -> (Context, EventsLoop) {
-        let configuration = conf::Conf {
-            window_mode: WindowMode::default().dimensions(640f32, 320f32),
-            window_setup: WindowSetup::default().title("CHIP-8 Emulator").vsync(true),
-            ..Default::default()
+    //  Executes a single instruction and returns what a tracer would record
+    //  for it. The boot jump is run first when the CPU is sitting on it, so
+    //  the first record is the program's first instruction, as in traces
+    //  from emulators that start at 0x200.
+    pub fn step_with_record(&mut self) -> TraceRecord {
+        if self.program_counter == 0 && self.read_opcode() == BOOT_JUMP {
+            self.step();
+        }
+        let (pc, op_code) = (self.program_counter, self.read_opcode());
+        self.step();
+        self.trace_record(pc, op_code)
+    }
+
+    //  Executes what is left of the current 60 Hz frame
+    pub fn run_frame(&mut self) {
+        let frame = self.frame_count;
+        while self.frame_count == frame && !self.halted {
+            self.advance();
+        }
+    }
+
+    fn trace_record(&self, pc: usize, op_code: u16) -> TraceRecord {
+        TraceRecord {
+            cycle: self.cycles,
+            pc: pc as u16,
+            opcode: op_code,
+            disassembly: disassembler::disassemble(op_code),
+            registers: self.registers,
+            i: self.pointer_register,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            columns: Columns::all(),
+        }
+    }
+
+    fn trace(&mut self, pc: usize, op_code: u16) {
+        let record = self.trace_record(pc, op_code);
+        let tracer = self.tracer.as_mut().unwrap();
+        let result = match self.halted {
+            true => tracer.record(&record).and_then(|_| tracer.flush()),
+            false => tracer.record(&record),
         };
+        if let Err(e) = result {
+            self.trace_error = Some(e);
+            self.tracer = None;
+        }
+    }
+
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+        self.trace_error = None;
+    }
+
+    //  The write that failed, after which the rest of the run went untraced
+    pub fn trace_error(&self) -> Option<&io::Error> {
+        self.trace_error.as_ref()
+    }
+
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
 
-        ContextBuilder::new("CHIP-8 Emulator", "")
-            .conf(configuration)
-            .build()
-            .unwrap()
+    fn frame_budget(&self) -> usize {
+        match self.timing {
+            TimingMode::Instructions => self.cycles_per_frame,
+            TimingMode::CosmacVip => timing::VIP_CYCLES_AVAILABLE_PER_FRAME,
+        }
+    }
+
+    fn end_frame(&mut self) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.end_frame();
+        }
+        self.tick_timers();
+        self.frame_count += 1;
+        self.waiting_for_vblank = false;
     }
 
-    fn emulate_cycle(&mut self, mut ctx: &mut Context) {
+    //  Empties the stack and gives it room for `depth` return addresses,
+    //  kept in `storage`
+    pub fn set_stack(&mut self, depth: usize, storage: StackStorage) -> Result<(), String> {
+        self.stack = Stack::new(depth, storage)?;
+        Ok(())
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn set_timing(&mut self, timing: TimingMode) {
+        self.timing = timing;
+        self.frame_cycles = 0;
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: usize) {
+        self.cycles_per_frame = cycles_per_frame.max(1);
+    }
+
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        //  There is no sound output; the timer only counts down
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    pub fn builder() -> CPUBuilder {
+        CPUBuilder::new()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    //  `length` bytes from `start`, cut short at the end of memory
+    pub fn memory_range(&self, start: u16, length: usize) -> &[u8] {
+        let start = (start as usize).min(self.memory.len());
+        let end = start.saturating_add(length).min(self.memory.len());
+        &self.memory[start..end]
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter as u16
+    }
+
+    pub fn pointer_register(&self) -> u16 {
+        self.pointer_register
+    }
+
+    //  The return addresses currently on the stack, oldest first
+    pub fn stack(&self) -> Vec<u16> {
+        self.stack.addresses(&self.memory)
+    }
+
+    pub fn stack_pointer(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.depth()
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn keypad(&self) -> &[bool; 16] {
+        &self.keypad
+    }
+
+    //  Whether an FX0A instruction is still waiting for a key
+    pub fn waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.display
+    }
+
+    pub fn display_size(&self) -> (usize, usize) {
+        (self.display.width(), self.display.height())
+    }
+
+    //  Whether the display changed since the last call
+    pub fn take_display_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.display_dirty, false)
+    }
+
+    //  Puts the machine back in its power-on state with `rom` loaded at
+    //  0x200. Quirks, timing and attached tools are kept.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        if rom.len() > self.memory.len() - 0x200 {
+            return Err(format!("ROM of {} bytes does not fit in memory", rom.len()));
+        }
+        let mut fresh = CPU::new_with_memory(rom.to_vec());
+        fresh.quirks = self.quirks;
+        fresh.stack = Stack::new(self.stack.depth(), self.stack.storage()).unwrap();
+        fresh.machine_code = self.machine_code;
+        fresh.timing = self.timing;
+        fresh.cycles_per_frame = self.cycles_per_frame;
+        fresh.set_font(self.font.clone());
+        fresh.memory_map = self.memory_map.clone();
+        fresh.strict = self.strict;
+        fresh.rng = self.rng.clone();
+        fresh.set_decode_cache(self.decoded.is_some());
+        fresh.set_basic_blocks(self.blocks.is_some());
+        fresh.tracer = self.tracer.take();
+        fresh.trace_error = self.trace_error.take();
+        fresh.profiler = self.profiler.take();
+        fresh.coverage = self.coverage.take();
+        *self = fresh;
+        Ok(())
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    //  Why the program stopped, if it failed rather than halted with 0000
+    pub fn error(&self) -> Option<ExecutionError> {
+        self.error
+    }
+
+    fn fail(&mut self, error: ExecutionError) {
+        self.error = Some(error);
+        self.halted = true;
+    }
+
+    fn emulate_cycle(&mut self) -> Result<(), ExecutionError> {
+        let pc = self.program_counter;
+        if self.strict && !self.memory_map.is_executable(pc) {
+            let region = self.memory_map.region_at(pc);
+            return Err(ExecutionError::ExecutedData { address: pc as u16, region });
+        }
         let op_code = self.read_opcode();
         self.program_counter += 2;
 
-        let (c, x, y, d) = decompose_opcode(op_code);
-        let nnn = op_code & 0x0FFF;
-        let kk = (op_code & 0x00FF) as u8;
-
-        match (c, x, y, d) {
-            (0x0, 0x0, 0x0, 0x0) => event::quit(&mut ctx),
-            (0x0, 0x0, 0xE, 0x0) => self.clear_display(),
-            (0x0, 0x0, 0xE, 0xE) => self.ret(),
-            (0x1, _, _, _) => self.jump_to(nnn),
-            (0x2, _, _, _) => self.call(nnn),
-            (0x3, _, _, _) => self.skip_if_equal(x, kk),
-            (0x4, _, _, _) => self.skip_if_different(x, kk),
-            (0x5, _, _, 0x0) => self.skip_if_equal_registers(x, y),
-            (0x6, _, _, _) => self.load_in_register(x, kk),
-            (0x7, _, _, _) => self.add_constant(x, kk),
-            (0x8, _, _, 0x0) => self.copy_second_to_first(x, y),
-            (0x8, _, _, 0x1) => self.or(x, y),
-            (0x8, _, _, 0x2) => self.and(x, y),
-            (0x8, _, _, 0x3) => self.xor(x, y),
-            (0x8, _, _, 0x4) => self.add_registers(x, y),
-            (0x8, _, _, 0x5) => self.sub_registers(x, y),
-            (0x8, _, _, 0x6) => self.shift_right(x),
-            (0x8, _, _, 0x7) => self.sub_registers_swapped(x, y),
-            (0x8, _, _, 0xE) => self.shift_left(x),
-            (0x9, _, _, 0x0) => self.skip_if_different_registers(x, y),
-            (0xA, _, _, _) => self.set_pointer_register(nnn),
-            (0xB, _, _, _) => self.offset_jump_to(nnn),
-            (0xC, _, _, _) => self.random_and_constant_in(x, kk),
-            (0xD, _, _, _) => self.draw_at(x, y, d),
-            (0xF, _, 0x0, 0x7) => self.store_delay_timer_in(x),
-            (0xF, _, 0x0, 0xA) => self.wait_and_store_key_in(x),
-            (0xF, _, 0x1, 0x5) => self.load_delay_timer_from(x),
-            (0xF, _, 0x1, 0xE) => self.add_to_pointer_register(x),
-            (0xF, _, 0x2, 0x9) => self.point_to_font_char(x),
-            (0xF, _, 0x3, 0x3) => self.store_as_bcd(x),
-            (0xF, _, 0x5, 0x5) => self.store_registers_up_to(x),
-            (0xF, _, 0x6, 0x5) => self.load_registers_up_to(x),
-
-            _ => todo!("opcode {:04x}", op_code),
-        }
-    }
-
-    fn handle_event(&mut self, mut ctx: &mut Context, event: Event) {
-        let state = &mut self.display;
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    if !state.quit_event(&mut ctx) {
-                        ggez::event::quit(&mut ctx);
-                    }
-                }
+        match self.decode_at(pc, op_code) {
+            Ok(instruction) => self.execute(instruction),
+            Err(error) => Err(ExecutionError::UnknownOpcode { address: pc as u16, error }),
+        }
+    }
 
-                WindowEvent::KeyboardInput {
-                    input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(current_keycode),
-                        modifiers,
-                        ..
-                    },
-                    ..
-                } => {
-                    let repeat = keyboard::is_key_repeated(&mut ctx);
-                    state.key_down_event(&mut ctx, current_keycode, modifiers.into(), repeat);
-
-                    if self.waiting_for_input {
-                        match self.keycode_map.iter().enumerate().find(|&(_, &map_keycode)| {
-                            map_keycode == current_keycode
-                        }) {
-                            None => (),
-                            Some((value, _)) => {
-                                self.registers[self.input_register_index.unwrap()] = value as u8;
-                                self.waiting_for_input = false;
-                                self.input_register_index = None;
-                            }
-                        };
-                    }
-                }
+    //  Where the instruction being executed was fetched from
+    fn instruction_address(&self) -> u16 {
+        self.program_counter.wrapping_sub(2) as u16
+    }
 
-                _ => (),
+    //  In strict mode, fails unless every address is one the program may write to
+    fn check_writes(&self, mut addresses: impl Iterator<Item = usize>) -> Result<(), ExecutionError> {
+        if !self.strict {
+            return Ok(());
+        }
+        match addresses.find(|&address| !self.memory_map.region_at(address).is_writable()) {
+            Some(target) => Err(ExecutionError::ProtectedWrite {
+                address: self.instruction_address(),
+                target: target as u16,
+                region: self.memory_map.region_at(target),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    //  The addresses of `length` bytes from I. Like I itself they wrap
+    //  around the platform's address space; fails when any of them is
+    //  past the end of memory, which XO-CHIP's 16-bit space can be.
+    fn pointer_addresses(&self, length: usize) -> Result<impl Iterator<Item = usize> + Clone, ExecutionError> {
+        let (start, mask) = (self.pointer_register as usize, self.quirks.address_mask as usize);
+        let addresses = (0..length).map(move |offset| (start + offset) & mask);
+        match addresses.clone().all(|address| address < self.memory.len()) {
+            true => Ok(addresses),
+            false => Err(ExecutionError::MemoryOutOfBounds {
+                address: self.instruction_address(),
+                pointer: self.pointer_register,
+                length,
+            }),
+        }
+    }
+
+    fn decode_at(&mut self, pc: usize, op_code: u16) -> Result<Instruction, DecodeError> {
+        let cache = match self.decoded.as_mut() {
+            Some(cache) => cache,
+            None => return decode(op_code),
+        };
+        if let Some(instruction) = cache[pc] {
+            return Ok(instruction);
+        }
+        let instruction = decode(op_code)?;
+        cache[pc] = Some(instruction);
+        Ok(instruction)
+    }
+
+    //  Forgets the decoded instructions overlapping `length` bytes from
+    //  `address`, including the one starting on the byte before
+    fn invalidate_decoded(&mut self, address: usize, length: usize) {
+        if let Some(blocks) = self.blocks.as_mut() {
+            blocks.memory_written(address, length);
+        }
+        if let Some(cache) = self.decoded.as_mut() {
+            let end = (address + length).min(cache.len());
+            for entry in &mut cache[address.saturating_sub(1)..end] {
+                *entry = None;
             }
-            _ => (),
         }
     }
 
-    pub(in super) fn peek_register(&self, register_index: usize) -> u8 {
-        self.registers[register_index]
+    //  Runs straight-line code as basic blocks of instructions decoded once
+    //  (see blocks.rs) when running frames or headless. Single steps, and
+    //  runs with a tracer, profiler or coverage attached, are unaffected.
+    pub fn set_basic_blocks(&mut self, enabled: bool) {
+        self.blocks = match enabled {
+            true => Some(BlockCache::new(self.memory.len())),
+            false => None,
+        };
     }
 
+    //  How often writes to cached code threw the basic blocks away
+    pub fn block_cache_flushes(&self) -> u64 {
+        self.blocks.as_ref().map_or(0, BlockCache::flushes)
+    }
+
+    //  The map strict mode checks programs against; the standard one
+    //  unless set
+    pub fn set_memory_map(&mut self, memory_map: MemoryMap) {
+        self.memory_map = memory_map.with_font(self.font.range());
+    }
+
+    //  Replaces the font in memory, clearing wherever the old one was
+    pub fn set_font(&mut self, font: Font) {
+        let old = self.font.range();
+        self.memory[old.clone()].iter_mut().for_each(|byte| *byte = 0);
+        self.invalidate_decoded(old.start, old.len());
+        self.memory[font.range()].copy_from_slice(font.glyphs());
+        self.invalidate_decoded(font.range().start, font.glyphs().len());
+        self.memory_map = self.memory_map.clone().with_font(font.range());
+        self.font = font;
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn memory_map(&self) -> &MemoryMap {
+        &self.memory_map
+    }
+
+    //  Makes writes outside the program area and execution of anything but
+    //  code errors, which catches programs that go astray early
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_machine_code_policy(&mut self, policy: MachineCodePolicy) {
+        self.machine_code = policy;
+    }
+
+    //  Caches decoded instructions by address instead of decoding every
+    //  opcode as it is fetched. Writes through FX55 and FX33 invalidate
+    //  the entries they overwrite, so self-modifying programs still work.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded = match enabled {
+            true => Some(vec![None; self.memory.len()]),
+            false => None,
+        };
+    }
+
+    //  Executes `instruction` as if it had just been fetched, with the
+    //  program counter already past it
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), ExecutionError> {
+        use Instruction::*;
+
+        let address = self.instruction_address();
+        match instruction {
+            Halt => self.halted = true,
+            System { address: routine } => self.call_machine_code(routine)?,
+            ClearScreen => self.clear_display(),
+            Return => self.ret()?,
+            Jump { address } => self.jump_to(address),
+            Call { address } => self.call(address)?,
+            SkipIfEqual { x, value } => self.skip_if_equal(x, value),
+            SkipIfNotEqual { x, value } => self.skip_if_different(x, value),
+            SkipIfRegistersEqual { x, y } => self.skip_if_equal_registers(x, y),
+            Load { x, value } => self.load_in_register(x, value)?,
+            AddConstant { x, value } => self.add_constant(x, value),
+            Copy { x, y } => self.copy_second_to_first(x, y),
+            Or { x, y } => self.or(x, y),
+            And { x, y } => self.and(x, y),
+            Xor { x, y } => self.xor(x, y),
+            Add { x, y } => self.add_registers(x, y),
+            Sub { x, y } => self.sub_registers(x, y),
+            ShiftRight { x, y } => self.shift_right(x, y),
+            SubReversed { x, y } => self.sub_registers_swapped(x, y),
+            ShiftLeft { x, y } => self.shift_left(x, y),
+            SkipIfRegistersNotEqual { x, y } => self.skip_if_different_registers(x, y),
+            SetPointer { address } => self.set_pointer_register(address),
+            JumpWithOffset { address } => self.offset_jump_to(address),
+            Random { x, mask } => self.random_and_constant_in(x, mask),
+            Draw { x, y, n } => self.draw_at(x, y, n)?,
+            SkipIfKey { x } => self.skip_if_key_pressed(x),
+            SkipIfNotKey { x } => self.skip_if_key_not_pressed(x),
+            ReadDelayTimer { x } => self.store_delay_timer_in(x),
+            WaitForKey { x } => self.wait_and_store_key_in(x)?,
+            SetDelayTimer { x } => self.load_delay_timer_from(x),
+            SetSoundTimer { x } => self.load_sound_timer_from(x),
+            AddToPointer { x } => self.add_to_pointer_register(x),
+            FontCharacter { x } => self.point_to_font_char(x),
+            StoreBcd { x } => self.store_as_bcd(x)?,
+            StoreRegisters { x } => self.store_registers_up_to(x)?,
+            LoadRegisters { x } => self.load_registers_up_to(x)?,
+        }
+        //  Both bytes of the next opcode have to be in memory
+        match self.program_counter + 1 < self.memory.len() {
+            true => Ok(()),
+            false => Err(ExecutionError::ProgramCounterOutOfBounds {
+                address,
+                program_counter: self.program_counter,
+            }),
+        }
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keypad[key as usize] = pressed;
+    }
+
+    //  Bytes past the end of memory read as 0, for the benefit of tools
+    //  looking at a program that failed by running off the end
     fn read_opcode(&self) -> u16 {
         let pc = self.program_counter;
-        let op_byte1 = self.memory[pc] as u16;
-        let op_byte2 = self.memory[pc + 1] as u16;
+        let op_byte1 = self.memory.get(pc).copied().unwrap_or(0) as u16;
+        let op_byte2 = self.memory.get(pc + 1).copied().unwrap_or(0) as u16;
 
         op_byte1 << 8 | op_byte2
     }
 
+    //  The arithmetic instructions write VF after the result, so that with
+    //  VF as the destination the flag is what is left in it
+    fn set_result_and_flag(&mut self, register_index: u8, result: u8, flag: bool) {
+        self.registers[register_index as usize] = result;
+        self.registers[0xF] = flag as u8;
+    }
+
     fn add_registers(&mut self, x: u8, y: u8) {
         let first_arg = self.registers[x as usize];
         let second_arg = self.registers[y as usize];
 
         let (value, overflow) = first_arg.overflowing_add(second_arg);
-        self.registers[x as usize] = value;
-        match overflow {
-            true => self.registers[0xF] = 1,
-            false => self.registers[0xF] = 0,
-        }
+        self.set_result_and_flag(x, value, overflow);
     }
 
     fn add_constant(&mut self, x: u8, kk: u8) {
         self.registers[x as usize] = self.registers[x as usize].wrapping_add(kk);
     }
 
-    fn call(&mut self, fn_address: u16) {
-        let (stack_ptr, stack) = (self.stack_pointer, &mut self.stack);
-
-        if stack_ptr >= stack.len() {
-            panic!("Stack overflow!");
+    fn call(&mut self, fn_address: u16) -> Result<(), ExecutionError> {
+        match self.stack.push(self.program_counter as u16, &mut self.memory) {
+            Some(Some(slot)) => self.invalidate_decoded(slot, 2),
+            Some(None) => (),
+            None => return Err(ExecutionError::StackOverflow { address: self.instruction_address() }),
         }
-
-        stack[stack_ptr] = self.program_counter as u16;
-        self.stack_pointer += 1;
         self.program_counter = fn_address as usize;
+        Ok(())
     }
 
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow!");
+    fn ret(&mut self) -> Result<(), ExecutionError> {
+        match self.stack.pop(&self.memory) {
+            Some(address) => self.program_counter = address as usize,
+            None => return Err(ExecutionError::StackUnderflow { address: self.instruction_address() }),
         }
-
-        self.stack_pointer -= 1;
-        self.program_counter = self.stack[self.stack_pointer] as usize;
+        Ok(())
     }
 
+    //  A destination outside memory fails when the next opcode is fetched
     fn jump_to(&mut self, address: u16) {
-        if !self.is_legal_address(address as usize) {
-            panic!("Jumping to illegal address!")
-        }
         self.program_counter = address as usize;
     }
 
-    fn is_legal_address(&self, address: usize) -> bool {
-        address < self.memory.len() - 1
-    }
-
     fn set_pointer_register(&mut self, address: u16) {
         self.pointer_register = address;
     }
 
     fn offset_jump_to(&mut self, address: u16) {
-        let destination = address + self.registers[0] as u16;
-        if !self.is_legal_address(destination as usize) {
-            panic!("Jumping to illegal address!")
-        }
+        let offset_register = match self.quirks.jump {
+            true => ((address & 0x0F00) >> 8) as usize,
+            false => 0,
+        };
+        let destination = address + self.registers[offset_register] as u16;
         self.jump_to(destination);
     }
 
-    fn load_in_register(&mut self, register_index: u8, register_value: u8) {
+    fn load_in_register(&mut self, register_index: u8, register_value: u8) -> Result<(), ExecutionError> {
         let index = register_index as usize;
         if index >= self.registers.len() {
-            panic!("Register index out of bounds!")
+            return Err(ExecutionError::InvalidRegister { address: self.instruction_address(), register: register_index });
         }
         self.registers[index] = register_value;
+        Ok(())
     }
 
     fn skip_if_equal(&mut self, register_index: u8, comparison_value: u8) {
@@ -358,151 +753,366 @@ impl CPU {
     fn or(&mut self, first_index: u8, second_index: u8) {
         let (first, second) = (first_index as usize, second_index as usize);
         self.registers[first] |= self.registers[second];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn and(&mut self, first_index: u8, second_index: u8) {
         let (first, second) = (first_index as usize, second_index as usize);
         self.registers[first] &= self.registers[second];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn xor(&mut self, first_index: u8, second_index: u8) {
         let (first, second) = (first_index as usize, second_index as usize);
         self.registers[first] ^= self.registers[second];
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
     }
 
+    //  VF is set when there is NO borrow
     fn sub_registers(&mut self, first_index: u8, second_index: u8) {
         let (first, second) = (first_index as usize, second_index as usize);
-        let (result, overflowing) = self.registers[first].overflowing_sub(self.registers[second]);
-        self.registers[first] = result;
-        match overflowing {
-            true => self.registers[0xF] = 1,
-            false => self.registers[0xF] = 0,
-        }
+        let (result, borrow) = self.registers[first].overflowing_sub(self.registers[second]);
+        self.set_result_and_flag(first_index, result, !borrow);
     }
 
-    fn shift_right(&mut self, first_index: u8) {
-        let first = first_index as usize;
-        let first_register = self.registers[first];
-        self.registers[0xF] = first_register & 0b0000_0001;
-        self.registers[first].shr_assign(1);
+    //  VF gets the bit shifted out
+    fn shift_right(&mut self, first_index: u8, second_index: u8) {
+        let source = match self.quirks.shift {
+            true => self.registers[first_index as usize],
+            false => self.registers[second_index as usize],
+        };
+        self.set_result_and_flag(first_index, source >> 1, source & 0b0000_0001 != 0);
     }
 
-    fn shift_left(&mut self, first_index: u8) {
-        let first = first_index as usize;
-        let first_register = self.registers[first];
-        self.registers[0xF] = (first_register & 0b1000_0000) >> 7;
-        self.registers[first].shl_assign(1);
+    fn shift_left(&mut self, first_index: u8, second_index: u8) {
+        let source = match self.quirks.shift {
+            true => self.registers[first_index as usize],
+            false => self.registers[second_index as usize],
+        };
+        self.set_result_and_flag(first_index, source << 1, source & 0b1000_0000 != 0);
     }
 
+    //  VY - VX, with VF set when there is NO borrow
     fn sub_registers_swapped(&mut self, first_index: u8, second_index: u8) {
         let (first, second) = (first_index as usize, second_index as usize);
-        let (result, overflowing) = self.registers[second].overflowing_sub(self.registers[first]);
-        self.registers[first] = result;
-        match overflowing {
-            true => self.registers[0xF] = 1,
-            false => self.registers[0xF] = 0,
-        }
+        let (result, borrow) = self.registers[second].overflowing_sub(self.registers[first]);
+        self.set_result_and_flag(first_index, result, !borrow);
     }
 
     fn random_and_constant_in(&mut self, register_index: u8, constant: u8) {
-        let random_num: u8 = rand::thread_rng().gen();
+        let random_num: u8 = self.rng.gen();
         self.registers[register_index as usize] = random_num.bitand(constant);
     }
 
-    fn store_registers_up_to(&mut self, register_index: u8) {
+    fn store_registers_up_to(&mut self, register_index: u8) -> Result<(), ExecutionError> {
         let index = register_index as usize;
-        let start_address = self.pointer_register as usize;
-        let end_address = start_address + index;
-        if self.memory.len() < end_address {
-            panic!("Writing out of memory bounds.");
+        let addresses = self.pointer_addresses(index + 1)?;
+        self.check_writes(addresses.clone())?;
+        for (register, address) in (0..=index).zip(addresses) {
+            self.memory[address] = self.registers[register];
+            self.invalidate_decoded(address, 1);
         }
-        self.memory[start_address..=end_address].copy_from_slice(&self.registers[0..=index]);
+        self.advance_pointer_register_after_transfer(register_index);
+        Ok(())
     }
 
-    fn load_registers_up_to(&mut self, register_index: u8) {
+    fn load_registers_up_to(&mut self, register_index: u8) -> Result<(), ExecutionError> {
         let index = register_index as usize;
-        let start_address = self.pointer_register as usize;
-        let end_address = start_address + index;
-        if self.memory.len() < end_address {
-            panic!("Illegal read.");
+        for (register, address) in (0..=index).zip(self.pointer_addresses(index + 1)?) {
+            self.registers[register] = self.memory[address];
+        }
+        self.advance_pointer_register_after_transfer(register_index);
+        Ok(())
+    }
+
+    fn advance_pointer_register_after_transfer(&mut self, register_index: u8) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
         }
-        self.registers[0..=index].copy_from_slice(&self.memory[start_address..=end_address]);
+        let increment = match self.quirks.memory_increment_by_x {
+            true => register_index as u16,
+            false => register_index as u16 + 1,
+        };
+        self.pointer_register = self.pointer_register.wrapping_add(increment) & self.quirks.address_mask;
     }
 
     fn add_to_pointer_register(&mut self, register_index: u8) {
-        self.pointer_register += self.registers[register_index as usize] as u16;
+        let sum = self.pointer_register as usize + self.registers[register_index as usize] as usize;
+        let mask = self.quirks.address_mask as usize;
+        self.pointer_register = (sum & mask) as u16;
+        if self.quirks.pointer_overflow {
+            self.registers[0xF] = (sum > mask) as u8;
+        }
     }
 
-    fn store_as_bcd(&mut self, register_index: u8) {
-        let i = self.pointer_register as usize;
+    fn store_as_bcd(&mut self, register_index: u8) -> Result<(), ExecutionError> {
         let value = self.registers[register_index as usize];
         //  Note that u8 can't represent four-digit numbers, so there is no
         //  need to compute: value % 1000
-        self.memory[i + 0] = value / 100u8;
-        self.memory[i + 1] = (value % 100u8) / 10u8;
-        self.memory[i + 2] = value % 10u8;
+        let digits = [value / 100u8, (value % 100u8) / 10u8, value % 10u8];
+        let addresses = self.pointer_addresses(3)?;
+        self.check_writes(addresses.clone())?;
+        for (digit, address) in digits.iter().zip(addresses) {
+            self.memory[address] = *digit;
+            self.invalidate_decoded(address, 1);
+        }
+        Ok(())
     }
 
-    fn point_to_font_char(&mut self, register_index: u8) {
-        let char = self.registers[register_index as usize];
-        if char.div(16u8) > 0 {    //  if it is a number between 0 and 15
-            panic!("Unrepresentable character!");
+    fn call_machine_code(&mut self, routine: u16) -> Result<(), ExecutionError> {
+        let error = ExecutionError::MachineCodeRoutine { address: self.instruction_address(), routine };
+        match self.machine_code {
+            MachineCodePolicy::Ignore => Ok(()),
+            MachineCodePolicy::Error => Err(error),
         }
-        self.pointer_register = 2 + 5 * char as u16;
     }
 
-    fn draw_at(&mut self, first_index: u8, second_index: u8, byte_number: u8) {
-        let x_coord = self.registers[first_index as usize].rem(64u8);
-        let y_coord = self.registers[second_index as usize].rem(32u8);
-        self.registers[0xF] = 0;
-        let ptr_register = self.pointer_register as usize;
-        let sprite = &self.memory[ptr_register..ptr_register + (byte_number as usize)];
+    //  Like the COSMAC VIP, only looks at the low digit of the register
+    fn point_to_font_char(&mut self, register_index: u8) {
+        let char = self.registers[register_index as usize] & 0x0F;
+        self.pointer_register = self.font.character_address(char);
+    }
 
-        'rows: for i in 0..byte_number {
-            if y_coord + i > 32 {
-                break 'rows;
-            }
-            let byte = sprite[i as usize];
-            'cols: for j in 0..8 {
-                if x_coord + j > 64 {
-                    break 'cols;
-                }
-                let byte = byte.reverse_bits() as u8;
-                let current_bit = ((byte >> j) & 0x01) != 0;
-                let display_row = (y_coord + i) as usize;
-                let display_column = (x_coord + j) as usize;
-                let display_index = 64 * display_row + display_column;
-                let current_display_bit = self.display.data[display_index];
-                self.display.data[display_index as usize] = current_display_bit ^ current_bit;
-                self.registers[0xF] = (current_display_bit & current_bit) as u8;
-            }
+    fn draw_at(&mut self, first_index: u8, second_index: u8, byte_number: u8) -> Result<(), ExecutionError> {
+        let x_coord = self.registers[first_index as usize] as usize % self.display.width();
+        let y_coord = self.registers[second_index as usize] as usize % self.display.height();
+        let mut sprite = [0u8; 15];
+        for (row, address) in sprite.iter_mut().zip(self.pointer_addresses(byte_number as usize)?) {
+            *row = self.memory[address];
         }
 
-        self.display.dirty_bit = true;
+        let sprite = &sprite[..byte_number as usize];
+        let collision = self.display.draw_sprite(x_coord, y_coord, sprite, self.quirks.wrap);
+        self.registers[0xF] = collision as u8;
+
+        self.display_dirty = true;
+        //  The VIP interpreter always waits for the display interrupt before drawing
+        self.waiting_for_vblank = self.quirks.vblank || self.timing == TimingMode::CosmacVip;
+        Ok(())
     }
 
     fn clear_display(&mut self) {
-        self.display.data = [false; 2048];
+        self.display.clear();
+        self.display_dirty = true;
+    }
+
+    fn skip_if_key_pressed(&mut self, register_index: u8) {
+        let key = self.registers[register_index as usize] & 0x0F;
+        if self.keypad[key as usize] {
+            self.program_counter += 2;
+        }
+    }
+
+    fn skip_if_key_not_pressed(&mut self, register_index: u8) {
+        let key = self.registers[register_index as usize] & 0x0F;
+        if !self.keypad[key as usize] {
+            self.program_counter += 2;
+        }
     }
 
-    fn wait_and_store_key_in(&mut self, register_index: u8) {
-        self.waiting_for_input = true;
-        self.input_register_index = Some(register_index as usize);
+    fn wait_and_store_key_in(&mut self, register_index: u8) -> Result<(), ExecutionError> {
+        let next_state = match self.key_wait {
+            None | Some(KeyWait::ForPress) => {
+                match self.keypad.iter().position(|&pressed| pressed) {
+                    Some(key) => Some(KeyWait::ForRelease(key as u8)),
+                    None => Some(KeyWait::ForPress),
+                }
+            }
+            Some(KeyWait::ForRelease(key)) if !self.keypad[key as usize] => {
+                self.registers[register_index as usize] = key;
+                None
+            }
+            still_held => still_held,
+        };
+
+        self.key_wait = next_state;
+        //  Back to this instruction, which execute() may have been given
+        //  with a program counter that was never past it
+        if self.key_wait.is_some() {
+            self.program_counter = self.program_counter.checked_sub(2)
+                .ok_or(ExecutionError::ProgramCounterOutOfBounds {
+                    address: self.instruction_address(),
+                    program_counter: self.program_counter,
+                })?;
+        }
+        Ok(())
     }
 
     fn load_delay_timer_from(&mut self, register_index: u8) {
         self.delay_timer = self.registers[register_index as usize];
     }
 
+    fn load_sound_timer_from(&mut self, register_index: u8) {
+        self.sound_timer = self.registers[register_index as usize];
+    }
+
     fn store_delay_timer_in(&mut self, register_index: u8) {
         self.registers[register_index as usize] = self.delay_timer;
     }
 }
 
-fn decompose_opcode(op_code: u16) -> (u8, u8, u8, u8) {
-    let c = ((op_code & 0xF000) >> 12) as u8;
-    let x = ((op_code & 0x0F00) >> 8) as u8;
-    let y = ((op_code & 0x00F0) >> 4) as u8;
-    let d = ((op_code & 0x000F) >> 0) as u8;
-    (c, x, y, d)
+//  Builds a CPU in an arbitrary state, to set up precise tests. Unlike
+//  CPU::new, execution starts at 0x200 rather than at the jump to it.
+pub struct CPUBuilder {
+    cpu: CPU,
+    error: Option<String>,
+}
+
+impl CPUBuilder {
+    pub fn new() -> CPUBuilder {
+        let cpu = CPU { program_counter: 0x200, ..CPU::default() };
+        CPUBuilder { cpu, error: None }
+    }
+
+    pub fn registers(mut self, registers: [u8; 16]) -> Self {
+        self.cpu.registers = registers;
+        self
+    }
+
+    pub fn register(mut self, register_index: usize, value: u8) -> Self {
+        match self.cpu.registers.get_mut(register_index) {
+            Some(register) => *register = value,
+            None => self.fail(format!("there is no register V{:X}", register_index)),
+        }
+        self
+    }
+
+    pub fn program_counter(mut self, address: u16) -> Self {
+        if address as usize >= self.cpu.memory.len() - 1 {
+            self.fail(format!("program counter {:#05X} is out of memory", address));
+        }
+        self.cpu.program_counter = address as usize;
+        self
+    }
+
+    pub fn pointer_register(mut self, address: u16) -> Self {
+        self.cpu.pointer_register = address;
+        self
+    }
+
+    //  Copies `bytes` into memory from `address`
+    pub fn memory_at(mut self, address: u16, bytes: &[u8]) -> Self {
+        let start = address as usize;
+        match self.cpu.memory.get_mut(start..start + bytes.len()) {
+            Some(memory) => memory.copy_from_slice(bytes),
+            None => self.fail(format!("{} bytes at {:#05X} do not fit in memory", bytes.len(), address)),
+        }
+        self
+    }
+
+    //  Copies a program to 0x200
+    pub fn rom(self, rom: &[u8]) -> Self {
+        self.memory_at(0x200, rom)
+    }
+
+    //  Room for `depth` return addresses, kept in `storage`; comes before
+    //  `stack`, since it empties the stack
+    pub fn stack_layout(mut self, depth: usize, storage: StackStorage) -> Self {
+        if let Err(error) = self.cpu.set_stack(depth, storage) {
+            self.fail(error);
+        }
+        self
+    }
+
+    //  Return addresses, oldest first
+    pub fn stack(mut self, addresses: &[u16]) -> Self {
+        if addresses.len() > self.cpu.stack.depth() {
+            self.fail(format!("the stack holds at most {} addresses", self.cpu.stack.depth()));
+            return self;
+        }
+        for &address in addresses {
+            self.cpu.stack.push(address, &mut self.cpu.memory);
+        }
+        self
+    }
+
+    pub fn delay_timer(mut self, value: u8) -> Self {
+        self.cpu.delay_timer = value;
+        self
+    }
+
+    pub fn sound_timer(mut self, value: u8) -> Self {
+        self.cpu.sound_timer = value;
+        self
+    }
+
+    pub fn key(mut self, key: u8, pressed: bool) -> Self {
+        self.cpu.set_key(key & 0x0F, pressed);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.cpu.set_quirks(quirks);
+        self
+    }
+
+    pub fn timing(mut self, timing: TimingMode) -> Self {
+        self.cpu.set_timing(timing);
+        self
+    }
+
+    pub fn cycles_per_frame(mut self, cycles_per_frame: usize) -> Self {
+        self.cpu.set_cycles_per_frame(cycles_per_frame);
+        self
+    }
+
+    //  Makes CXKK produce the same numbers on every run
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.cpu.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn machine_code_policy(mut self, policy: MachineCodePolicy) -> Self {
+        self.cpu.set_machine_code_policy(policy);
+        self
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.cpu.set_font(font);
+        self
+    }
+
+    pub fn memory_map(mut self, memory_map: MemoryMap) -> Self {
+        self.cpu.set_memory_map(memory_map);
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.cpu.set_strict(strict);
+        self
+    }
+
+    pub fn decode_cache(mut self, enabled: bool) -> Self {
+        self.cpu.set_decode_cache(enabled);
+        self
+    }
+
+    pub fn basic_blocks(mut self, enabled: bool) -> Self {
+        self.cpu.set_basic_blocks(enabled);
+        self
+    }
+
+    //  Fails with the first invalid setting, if any
+    pub fn build(self) -> Result<CPU, String> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.cpu),
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+}
+
+impl Default for CPUBuilder {
+    fn default() -> Self {
+        CPUBuilder::new()
+    }
 }
@@ -1,5 +1,54 @@
 #[cfg(test)]
 use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::quirks::{Platform, Quirks};
+#[cfg(test)]
+use crate::cpu::timing::{self, TimingMode};
+#[cfg(test)]
+use crate::cpu::trace::{TraceFormat, TraceRecord, Tracer};
+#[cfg(test)]
+use std::{cell::RefCell, io, rc::Rc};
+
+//  Collects whatever the tracer writes so tests can look at it
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn traced_run(program: Vec<u8>, format: TraceFormat) -> Vec<String> {
+    traced_run_with_cache(program, format, false)
+}
+
+#[cfg(test)]
+fn traced_run_with_cache(program: Vec<u8>, format: TraceFormat, decode_cache: bool) -> Vec<String> {
+    let buffer = SharedBuffer::default();
+    let mut cpu = CPU::new_with_memory(program);
+    cpu.set_decode_cache(decode_cache);
+    cpu.set_tracer(Tracer::new(Box::new(buffer.clone()), format));
+    //  Bounded, so a program that never halts fails the test instead of hanging it
+    for _ in 0..10_000 {
+        if cpu.is_halted() {
+            break;
+        }
+        cpu.step();
+    }
+    assert!(cpu.is_halted());
+    let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    text.lines().map(String::from).collect()
+}
 
 #[test]
 fn rust_in_action_last_example() {
@@ -18,24 +67,54 @@ fn rust_in_action_last_example() {
     ]);
 
     let mut cpu = CPU::new(init_registers, init_memory.to_vec());
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 45);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 45);
 }
 
 
 #[test]
-#[should_panic(expected = "Stack overflow")]
 fn chip8_stack_overflows() {
-    let call_subroutine_command_x16 = vec![0x20, 0x00].repeat(16);
+    let call_subroutine_command_x16 = [0x20, 0x00].repeat(16);
     let mut cpu = CPU::new_with_memory(call_subroutine_command_x16);
-    cpu.run();
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::StackOverflow { address: 0x200 }));
+    assert_eq!(cpu.stack_pointer(), 16);
 }
 
 #[test]
-#[should_panic(expected = "Stack underflow")]
 fn chip8_stack_underflows() {
     let mut cpu = CPU::new_with_memory(vec![0x00, 0xEE]);
-    cpu.run();
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::StackUnderflow { address: 0x200 }));
+}
+
+#[test]
+fn drawing_at_the_bottom_edge_stays_on_screen(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x60, 0x00,     //  Set R0 to 0
+        0xF0, 0x29,     //  Point at the font sprite for 0
+        0x61, 0x1E,     //  Set R1 to 30, two rows above the bottom
+        0xD0, 0x15,     //  Draw the five rows at (R0, R1)
+        0x62, 0x01,     //  Set R2 to 1
+    ]);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[2], 1);
+    assert_eq!(cpu.registers()[0xF], 0);
+}
+
+#[test]
+fn stepping_a_halted_cpu_does_nothing(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x60, 0x05,     //  Set R0 to 5
+        0x00, 0x00,     //  Halt
+        0x61, 0x07,     //  Set R1 to 7, never reached
+    ]);
+    cpu.run_headless().unwrap();
+    let (pc, registers) = (cpu.program_counter(), *cpu.registers());
+    for _ in 0..3 {
+        cpu.step();
+    }
+    assert_eq!(cpu.program_counter(), pc);
+    assert_eq!(*cpu.registers(), registers);
+    assert_eq!(cpu.registers()[1], 0);
 }
 
 #[test]
@@ -47,23 +126,26 @@ fn offset_jump(){
         0x0F, 0xD0,
         0x70, 0x02      //  Add 0x02 to R0
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 9);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 9);
 }
 
 #[test]
-#[should_panic(expected = "illegal address")]
 fn illegal_jump(){
     //  Jumping to the last byte of memory shouldn't be allowed.
     let mut cpu = CPU::new_with_memory(vec![0x1F, 0xFF]);
-    cpu.run();
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ProgramCounterOutOfBounds {
+        address: 0x200,
+        program_counter: 0xFFF,
+    }));
+    assert!(cpu.is_halted());
 }
 
 #[test]
 fn load_number_to_register(){
     let mut cpu = CPU::new_with_memory(vec![0x60, 0xFF]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 0xFF);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 0xFF);
 }
 
 #[test]
@@ -75,8 +157,8 @@ fn skip_instructions_constant_comparison(){
         0x40, 0x07,     //  Skip if R0 does not contain 0x07
         0x70, 0x03      //  Add 0x03 to R0
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 10);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 10);
 }
 
 #[test]
@@ -89,9 +171,9 @@ fn skip_instructions_register_comparison(){
         0x90, 0x10,     //  Skip if R0 is not equal to R1
         0x80, 0x14      //  Add R0 to R1 and store result in R0
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 14);
-    assert_eq!(cpu.peek_register(1), 7);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 14);
+    assert_eq!(cpu.registers()[1], 7);
 }
 
 #[test]
@@ -101,9 +183,9 @@ fn register_copy(){
         0x81, 0x00,     //  Copies R0 to R1
         0x71, 0x02      //  Add 0x02 to R1
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 16);
-    assert_eq!(cpu.peek_register(1), 18);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 16);
+    assert_eq!(cpu.registers()[1], 18);
 }
 
 #[test]
@@ -117,10 +199,10 @@ fn logical_operators(){
         0x63, 0b0000_1100,      //  Set R3 to 0x0C
         0x82, 0x33              //  R2 ^ R3
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 6);
-    assert_eq!(cpu.peek_register(1), 4);
-    assert_eq!(cpu.peek_register(2), 8);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 6);
+    assert_eq!(cpu.registers()[1], 4);
+    assert_eq!(cpu.registers()[2], 8);
 }
 
 #[test]
@@ -130,11 +212,13 @@ fn registers_subtraction(){
         0x61, 0x08,     //  Set R1 to 8
         0x62, 0x01,     //  Set R2 to 1
         0x80, 0x15,     //  Subtract R1 from R0
-        0x82, 0x05,     //  Subtract R0 from R2
+        0x82, 0x05,     //  Subtract R0 from R2 (borrow)
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0x0), 2);
-    assert_eq!(cpu.peek_register(0xF), 1);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0x0], 2);
+    assert_eq!(cpu.registers()[0x2], 255);
+    //  VF is cleared by the borrow
+    assert_eq!(cpu.registers()[0xF], 0);
 }
 
 #[test]
@@ -148,11 +232,11 @@ fn shifting_registers(){
         0x62, 0xFF,     //  Set R2 to 255
         0x82, 0x0E,     //  Shift left R2  (overflow)
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0x0), 0);
-    assert_eq!(cpu.peek_register(0x1), 2);
-    assert_eq!(cpu.peek_register(0x2), 254);
-    assert_eq!(cpu.peek_register(0xF), 1);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0x0], 0);
+    assert_eq!(cpu.registers()[0x1], 2);
+    assert_eq!(cpu.registers()[0x2], 254);
+    assert_eq!(cpu.registers()[0xF], 1);
 }
 
 #[test]
@@ -160,11 +244,12 @@ fn registers_swap_subtraction(){
     let mut cpu = CPU::new_with_memory(vec![
         0x60, 0x0A,     //  set register 0 to 10
         0x61, 0x08,     //  set register 1 to 8
-        0x81, 0x07,     //  subtract register 0 from register 1
+        0x81, 0x07,     //  subtract register 1 from register 0, into register 1
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0x1), 2);
-    assert_eq!(cpu.peek_register(0xF), 0);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0x1], 2);
+    //  No borrow
+    assert_eq!(cpu.registers()[0xF], 1);
 }
 
 #[test]
@@ -179,11 +264,11 @@ fn load_and_store_operations(){
         0xA3, 0x02,     //  Set pointer register to 0x302
         0xF1, 0x65,     //  Load register from 0 to 1 reading from memory[pointer_register]
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0x0), 1);
-    assert_eq!(cpu.peek_register(0x1), 4);
-    assert_eq!(cpu.peek_register(0x2), 1);
-    assert_eq!(cpu.peek_register(0x3), 4);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0x0], 1);
+    assert_eq!(cpu.registers()[0x1], 4);
+    assert_eq!(cpu.registers()[0x2], 1);
+    assert_eq!(cpu.registers()[0x3], 4);
 }
 
 #[test]
@@ -198,35 +283,36 @@ fn add_to_pointer_register(){
         0xF2, 0x1E,     //  Add R2 to pointer register
         0xF1, 0x65,     //  Load register from 0 to 1 reading from memory[pointer_register]
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0x0), 2);
-    assert_eq!(cpu.peek_register(0x1), 4);
-    assert_eq!(cpu.peek_register(0x2), 2);
-    assert_eq!(cpu.peek_register(0x3), 4);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0x0], 2);
+    assert_eq!(cpu.registers()[0x1], 4);
+    assert_eq!(cpu.registers()[0x2], 2);
+    assert_eq!(cpu.registers()[0x3], 4);
 }
 
 #[test]
-#[should_panic]
-fn illegal_read_through_load(){
+fn load_through_a_pointer_past_the_end_of_memory_wraps(){
     let mut cpu = CPU::new_with_memory(vec![
         0xAF, 0xFF,     //  Set pointer register to 0xFFF
         0x60, 0x0A,     //  Set R0 to 10
-        0xF0, 0x1E,     //  Add R0 to pointer register
-        0xFA, 0x65,     //  Load starting from address 4105 (panic)
+        0xF0, 0x1E,     //  Add R0 to pointer register, which wraps to 0x009
+        0xFA, 0x65,     //  Load R0 to RA from 0x009
     ]);
-    cpu.run();
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.pointer_register(), 0x009);
+    assert_eq!(&cpu.registers()[..=0xA], &cpu.memory()[0x009..=0x013]);
 }
 
 #[test]
-#[should_panic]
-fn illegal_write_through_store(){
+fn store_through_a_pointer_past_the_end_of_memory_wraps(){
     let mut cpu = CPU::new_with_memory(vec![
         0xAF, 0xFF,     //  Set pointer register to 0xFFF
         0x60, 0x0A,     //  Set R0 to 10
-        0xF0, 0x1E,     //  Add R0 to pointer register
-        0xFA, 0x55,     //  Store starting at address 4105 (panic)
+        0xF0, 0x1E,     //  Add R0 to pointer register, which wraps to 0x009
+        0xFA, 0x55,     //  Store R0 to RA at 0x009
     ]);
-    cpu.run();
+    cpu.run_headless().unwrap();
+    assert_eq!(&cpu.memory()[0x009..=0x013], &[10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
 }
 
 #[test]
@@ -237,8 +323,318 @@ fn storing_as_bcd(){
         0xF0, 0x33,     //  Store R0 as BCD
         0xF2, 0x65,     //  Load in registers up to R2
     ]);
-    cpu.run();
-    assert_eq!(cpu.peek_register(0), 1);
-    assert_eq!(cpu.peek_register(1), 9);
-    assert_eq!(cpu.peek_register(2), 2);
-}
\ No newline at end of file
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 1);
+    assert_eq!(cpu.registers()[1], 9);
+    assert_eq!(cpu.registers()[2], 2);
+}
+
+#[test]
+fn wait_for_key_completes_on_release(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0xF3, 0x0A,     //  Wait for a key and store it in R3
+        0x00, 0x00,
+    ]);
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.program_counter(), 0x200);
+
+    cpu.set_key(0x7, true);
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.program_counter(), 0x200);
+    assert_eq!(cpu.registers()[3], 0);
+
+    cpu.set_key(0x7, false);
+    cpu.step();
+    assert_eq!(cpu.program_counter(), 0x202);
+    assert_eq!(cpu.registers()[3], 0x7);
+    cpu.run_headless().unwrap();
+}
+
+#[test]
+fn wait_for_key_ignores_other_keys_while_held(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0xF0, 0x0A,     //  Wait for a key and store it in R0
+        0x00, 0x00,
+    ]);
+    cpu.step();         //  Jump to 0x200
+    cpu.set_key(0xA, true);
+    cpu.step();
+    cpu.set_key(0x2, true);
+    cpu.step();
+    cpu.set_key(0x2, false);
+    cpu.step();
+    assert_eq!(cpu.program_counter(), 0x200);
+
+    cpu.set_key(0xA, false);
+    cpu.step();
+    assert_eq!(cpu.registers()[0], 0xA);
+}
+
+#[test]
+fn timers_keep_counting_while_waiting_for_key(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x60, 0x0A,     //  Set R0 to 10
+        0xF0, 0x15,     //  Load the delay timer from R0
+        0xF1, 0x0A,     //  Wait for a key and store it in R1
+        0xF2, 0x07,     //  Store the delay timer in R2
+        0x00, 0x00,
+    ]);
+    for _ in 0..6 {
+        cpu.step();     //  Jump to 0x200, two instructions, then three waiting cycles
+    }
+    assert_eq!(cpu.program_counter(), 0x204);
+    assert_eq!(cpu.delay_timer(), 6);
+    cpu.set_key(0x1, true);
+    cpu.step();
+    cpu.set_key(0x1, false);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[1], 0x1);
+    assert_eq!(cpu.registers()[2], 4);
+}
+
+#[test]
+fn skip_on_key_state(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x61, 0x05,     //  Set R1 to 5
+        0xE1, 0x9E,     //  Skip if key R1 is pressed
+        0x70, 0x01,     //  Add 1 to R0
+        0xE1, 0xA1,     //  Skip if key R1 is not pressed
+        0x70, 0x10,     //  Add 16 to R0
+    ]);
+    cpu.set_key(0x5, true);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 0x10);
+
+    let mut cpu = CPU::new_with_memory(vec![
+        0x61, 0x05,     //  Set R1 to 5
+        0xE1, 0x9E,     //  Skip if key R1 is pressed
+        0x70, 0x01,     //  Add 1 to R0
+        0xE1, 0xA1,     //  Skip if key R1 is not pressed
+        0x70, 0x10,     //  Add 16 to R0
+    ]);
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 0x01);
+}
+
+#[test]
+fn shift_quirk_selects_the_source_register(){
+    let program = vec![
+        0x60, 0x01,     //  Set R0 to 1
+        0x61, 0x40,     //  Set R1 to 64
+        0x80, 0x1E,     //  Shift left (R0 or R1) into R0
+    ];
+    let mut cpu = CPU::new_with_memory(program.clone());
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 0x02);
+
+    let mut cpu = CPU::new_with_memory(program);
+    cpu.set_quirks(Quirks { shift: false, ..Quirks::default() });
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0], 0x80);
+}
+
+#[test]
+fn memory_quirks_move_the_pointer_register(){
+    let program = vec![
+        0xA3, 0x00,     //  Set pointer register to 0x300
+        0xF2, 0x55,     //  Store R0 to R2
+        0x60, 0x07,     //  Set R0 to 7
+        0xF0, 0x55,     //  Store R0
+        0xA3, 0x00,     //  Set pointer register to 0x300
+        0xF3, 0x65,     //  Load R0 to R3
+    ];
+    let mut cpu = CPU::new_with_memory(program.clone());
+    cpu.set_quirks(Platform::ModernChip8.quirks());
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[3], 7);
+
+    let mut cpu = CPU::new_with_memory(program);
+    cpu.set_quirks(Platform::Chip48.quirks());
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[2], 7);
+}
+
+#[test]
+fn jump_quirk_uses_vx(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x60, 0x02,     //  Set R0 to 2
+        0x62, 0x04,     //  Set R2 to 4
+        0xB2, 0x04,     //  Jump to 0x204 plus R0 (or R2)
+        0x71, 0x01,     //  Add 1 to R1
+        0x71, 0x10,     //  Add 16 to R1
+    ]);
+    cpu.set_quirks(Quirks { jump: true, ..Quirks::default() });
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[1], 0x10);
+}
+
+#[test]
+fn logic_quirk_resets_vf(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0x6F, 0x05,     //  Set RF to 5
+        0x80, 0x11,     //  R0 | R1
+    ]);
+    cpu.set_quirks(Quirks { logic: true, ..Quirks::default() });
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[0xF], 0);
+}
+
+#[test]
+fn vblank_quirk_ends_the_frame_after_drawing(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0xD0, 0x01,     //  Draw a 1-byte sprite
+        0x70, 0x01,     //  Add 1 to R0
+        0x70, 0x01,     //  Add 1 to R0
+    ]);
+    cpu.set_quirks(Quirks { vblank: true, ..Quirks::default() });
+    cpu.set_cycles_per_frame(10);
+    cpu.run_frame();
+    assert_eq!(cpu.program_counter(), 0x202);
+    cpu.run_frame();
+    assert_eq!(cpu.registers()[0], 2);
+}
+
+#[test]
+fn vip_timing_charges_machine_cycles(){
+    //  Loads the delay timer, then spins on 6XNN for a while
+    let mut program = vec![
+        0x60, 0xFF,     //  Set R0 to 255
+        0xF0, 0x15,     //  Load the delay timer from R0
+    ];
+    program.extend([0x61, 0x00].repeat(400));
+    program.extend([0xF2, 0x07]);   //  Store the delay timer in R2
+    let mut cpu = CPU::new_with_memory(program);
+    cpu.set_timing(TimingMode::CosmacVip);
+    cpu.run_headless().unwrap();
+
+    let spinning = 400 * timing::vip_machine_cycles(0x6100, &[0; 16]);
+    let elapsed = spinning / timing::VIP_CYCLES_AVAILABLE_PER_FRAME;
+    assert_eq!(elapsed, 7);
+    //  Plus or minus the frame the timer was loaded in
+    let remaining = cpu.registers()[2] as usize;
+    assert!(remaining == 255 - elapsed || remaining == 255 - elapsed - 1);
+}
+
+#[test]
+fn vip_timing_waits_for_vblank_on_draw(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0xD0, 0x01,     //  Draw a 1-byte sprite
+        0x70, 0x01,     //  Add 1 to R0
+    ]);
+    cpu.set_timing(TimingMode::CosmacVip);
+    cpu.step();         //  Jump to 0x200
+    cpu.run_frame();
+    assert_eq!(cpu.program_counter(), 0x202);
+    cpu.step();
+    assert_eq!(cpu.registers()[0], 1);
+}
+
+#[test]
+fn vip_sprite_cost_depends_on_alignment(){
+    let mut registers = [0u8; 16];
+    let aligned = timing::vip_machine_cycles(0xD015, &registers);
+    registers[0] = 3;
+    let shifted = timing::vip_machine_cycles(0xD015, &registers);
+    assert!(shifted > aligned);
+    assert!(timing::vip_machine_cycles(0xD01F, &registers) > shifted);
+}
+
+#[test]
+fn trace_as_json_lines(){
+    let lines = traced_run(vec![
+        0x60, 0x2A,     //  Set R0 to 42
+        0xA3, 0x00,     //  Set pointer register to 0x300
+    ], TraceFormat::JsonLines);
+    //  The two instructions and the final 0000, without the boot jump
+    assert_eq!(lines.len(), 3);
+    let record: TraceRecord = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(record.cycle, 2);
+    assert_eq!(record.pc, 0x200);
+    assert_eq!(record.opcode, 0x602A);
+    assert_eq!(record.disassembly, "LD V0, 0x2A");
+    assert_eq!(record.registers[0], 42);
+    let record: TraceRecord = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(record.i, 0x300);
+}
+
+#[test]
+fn trace_as_csv(){
+    let lines = traced_run(vec![0x61, 0x07], TraceFormat::Csv);
+    assert_eq!(lines[0], TraceRecord::csv_header());
+    assert!(lines[1].starts_with("2,0x200,0x6107,\"LD V1, 0x07\",0,7,"));
+    assert!(lines[1].ends_with(",0x000,0,0"));
+}
+
+//  A trace file on a full disk
+#[cfg(test)]
+struct FailingWriter;
+
+#[cfg(test)]
+impl io::Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("no space left"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_trace_writes_stop_tracing_but_not_the_program(){
+    let mut cpu = CPU::new_with_memory(vec![0x61, 0x07]);
+    cpu.set_tracer(Tracer::new(Box::new(FailingWriter), TraceFormat::Text));
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.trace_error().map(|e| e.to_string()), Some("no space left".to_string()));
+    assert_eq!(cpu.registers()[1], 7);
+}
+
+#[test]
+fn trace_as_text(){
+    let lines = traced_run(vec![0x61, 0x07], TraceFormat::Text);
+    assert!(lines[0].contains("200  6107  LD V1, 0x07"));
+    assert!(lines[0].contains("V1=07"));
+}
+
+#[cfg(test)]
+fn self_modifying_program() -> Vec<u8> {
+    vec![
+        0x62, 0x00,     //  Set R2 to 0, the pass counter
+        0x63, 0x07,     //  0x202: set R3 to 7, rewritten to set it to 9
+        0x72, 0x01,     //  Increment R2
+        0x32, 0x02,     //  Skip the jump on the second pass
+        0x12, 0x0E,     //  Jump to 0x20E
+        0xF3, 0x33,     //  Store R3 as BCD, which turns 0x202 into a halt
+        0x12, 0x02,     //  Jump to it
+        0x60, 0x63,     //  0x20E: R0 and R1 hold the opcode 6309
+        0x61, 0x09,
+        0xA2, 0x02,     //  Point at 0x202
+        0xF1, 0x55,     //  Overwrite the instruction there
+        0x12, 0x02,     //  Jump back to it
+    ]
+}
+
+#[test]
+fn decode_cache_sees_self_modifying_code(){
+    let mut cpu = CPU::new_with_memory(self_modifying_program());
+    cpu.set_decode_cache(true);
+    //  Stale entries would keep the program looping
+    for _ in 0..100 {
+        cpu.step();
+        if cpu.is_halted() {
+            break;
+        }
+    }
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.registers()[3], 9);
+    assert_eq!(cpu.program_counter(), 0x204);
+}
+
+#[test]
+fn decode_cache_produces_identical_traces(){
+    let plain = traced_run_with_cache(self_modifying_program(), TraceFormat::Csv, false);
+    let cached = traced_run_with_cache(self_modifying_program(), TraceFormat::Csv, true);
+    assert_eq!(plain, cached);
+}
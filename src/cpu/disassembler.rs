@@ -0,0 +1,18 @@
+use crate::cpu::instruction::decode;
+
+//  Cowgod's mnemonics; anything that is not an instruction is shown as a
+//  data word.
+pub fn disassemble(op_code: u16) -> String {
+    match decode(op_code) {
+        Ok(instruction) => instruction.to_string(),
+        Err(_) => format!("DW 0x{:04X}", op_code),
+    }
+}
+
+//  The mnemonic alone, which groups instructions into classes
+pub fn mnemonic(op_code: u16) -> &'static str {
+    match decode(op_code) {
+        Ok(instruction) => instruction.mnemonic(),
+        Err(_) => "DW",
+    }
+}
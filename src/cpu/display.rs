@@ -0,0 +1,114 @@
+//  Bits per word of a row
+const WORD_BITS: usize = 64;
+
+//  A monochrome display, packed one bit per pixel into 64-bit words. Every
+//  row is `width / 64` words; the leftmost pixel is the most significant bit
+//  of the first word, so sprite bytes line up with it as they are stored.
+//  The widths of the CHIP-8 family, 64 and 128, are whole words; XO-CHIP's
+//  second plane would be another Framebuffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        assert!(width > 0 && width & (WORD_BITS - 1) == 0, "display width {} is not a multiple of 64", width);
+        Framebuffer {
+            words: vec![0; width / WORD_BITS * height],
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn words_per_row(&self) -> usize {
+        self.width / WORD_BITS
+    }
+
+    //  The words of row `y`, leftmost first
+    pub fn row(&self, y: usize) -> &[u64] {
+        let words = self.words_per_row();
+        &self.words[y * words..(y + 1) * words]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let word = self.row(y)[x / WORD_BITS];
+        word >> (WORD_BITS - 1 - x % WORD_BITS) & 1 != 0
+    }
+
+    //  Every pixel, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = bool> + '_ {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| self.pixel(x, y)))
+    }
+
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|word| *word = 0);
+    }
+
+    //  XORs an eight pixel wide sprite onto the display with its top left
+    //  corner at (`x`, `y`), which must be on the display. Whatever falls off
+    //  the right or bottom edge wraps around when `wrap` is set and is
+    //  clipped otherwise. Returns whether any lit pixel was turned off.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        let words = self.words_per_row();
+        let (word, offset) = (x / WORD_BITS, x % WORD_BITS);
+        //  The sprite's pixels in the word it starts in and, when it straddles
+        //  a word boundary, the next one
+        let spill_word = match word + 1 {
+            next if next < words => Some(next),
+            _ if wrap => Some(0),
+            _ => None,
+        };
+        let mut collision = false;
+        for (i, &byte) in sprite.iter().enumerate() {
+            let row = match y + i {
+                row if row < self.height => row,
+                _ if wrap => (y + i) % self.height,
+                _ => break,
+            };
+            let bits = (byte as u64) << (WORD_BITS - 8);
+            let start = row * words;
+            collision |= xor(&mut self.words[start + word], bits >> offset);
+            if offset > WORD_BITS - 8 {
+                if let Some(next) = spill_word {
+                    collision |= xor(&mut self.words[start + next], bits << (WORD_BITS - offset));
+                }
+            }
+        }
+        collision
+    }
+
+    //  Converts the display to RGBA bytes in `rgba`, which is reused so that
+    //  presenting a frame does not allocate once it has grown to size
+    pub fn write_rgba(&self, foreground: [u8; 4], background: [u8; 4], rgba: &mut Vec<u8>) {
+        rgba.clear();
+        rgba.reserve(self.width * self.height * 4);
+        for &word in self.words.iter() {
+            for bit in (0..WORD_BITS).rev() {
+                let colour = match word >> bit & 1 {
+                    0 => &background,
+                    _ => &foreground,
+                };
+                rgba.extend_from_slice(colour);
+            }
+        }
+    }
+}
+
+//  XORs `bits` into `word`, telling whether any of them were already set
+fn xor(word: &mut u64, bits: u64) -> bool {
+    let collision = *word & bits != 0;
+    *word ^= bits;
+    collision
+}
@@ -0,0 +1,84 @@
+#[cfg(test)]
+use crate::cpu::display::Framebuffer;
+
+#[cfg(test)]
+fn lit(framebuffer: &Framebuffer) -> Vec<(usize, usize)> {
+    let width = framebuffer.width();
+    framebuffer.pixels().enumerate()
+        .filter(|&(_, pixel)| pixel)
+        .map(|(index, _)| (index % width, index / width))
+        .collect()
+}
+
+#[test]
+fn sprites_are_packed_most_significant_bit_first(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(4, 1, &[0b1000_0001], false);
+    assert_eq!(framebuffer.row(1), &[0x0810_0000_0000_0000]);
+    assert_eq!(lit(&framebuffer), vec![(4, 1), (11, 1)]);
+}
+
+#[test]
+fn drawing_twice_erases_and_reports_the_collision(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    assert!(!framebuffer.draw_sprite(10, 3, &[0xFF, 0x81], false));
+    assert!(framebuffer.draw_sprite(10, 3, &[0xFF, 0x81], false));
+    assert!(lit(&framebuffer).is_empty());
+}
+
+#[test]
+fn a_collision_in_an_early_row_is_not_forgotten(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(0, 0, &[0x80], false);
+    //  Only the first of the three rows hits a lit pixel
+    assert!(framebuffer.draw_sprite(0, 0, &[0x80, 0x01, 0x01], false));
+}
+
+#[test]
+fn sprites_are_clipped_at_the_edges(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(60, 31, &[0xFF, 0xFF], false);
+    assert_eq!(lit(&framebuffer), vec![(60, 31), (61, 31), (62, 31), (63, 31)]);
+}
+
+#[test]
+fn sprites_wrap_around_the_edges_when_asked_to(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(62, 31, &[0b1110_0000, 0b0001_0000], true);
+    assert_eq!(lit(&framebuffer), vec![(1, 0), (0, 31), (62, 31), (63, 31)]);
+}
+
+#[test]
+fn wide_displays_draw_across_word_boundaries(){
+    let mut framebuffer = Framebuffer::new(128, 64);
+    framebuffer.draw_sprite(60, 40, &[0xFF], false);
+    assert_eq!(framebuffer.row(40), &[0x0000_0000_0000_000F, 0xF000_0000_0000_0000]);
+    assert!(framebuffer.draw_sprite(64, 40, &[0x80], false));
+    assert!(!framebuffer.pixel(64, 40));
+    assert!(framebuffer.pixel(67, 40));
+}
+
+#[test]
+fn clearing_turns_every_pixel_off(){
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(8, 8, &[0xFF; 8], false);
+    framebuffer.clear();
+    assert!(lit(&framebuffer).is_empty());
+}
+
+#[test]
+fn rgba_conversion_reuses_the_buffer(){
+    let (on, off) = ([1, 2, 3, 255], [0, 0, 0, 255]);
+    let mut framebuffer = Framebuffer::new(64, 32);
+    framebuffer.draw_sprite(1, 0, &[0x80], false);
+    let mut rgba = Vec::new();
+    framebuffer.write_rgba(on, off, &mut rgba);
+    assert_eq!(rgba.len(), 64 * 32 * 4);
+    assert_eq!(&rgba[..8], &[0, 0, 0, 255, 1, 2, 3, 255]);
+
+    let buffer = rgba.as_ptr();
+    framebuffer.clear();
+    framebuffer.write_rgba(on, off, &mut rgba);
+    assert_eq!(rgba.as_ptr(), buffer);
+    assert!(rgba.chunks(4).all(|pixel| pixel == off));
+}
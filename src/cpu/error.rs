@@ -0,0 +1,48 @@
+use std::fmt;
+use crate::cpu::instruction::DecodeError;
+use crate::cpu::memory_map::Region;
+
+//  Why the interpreter stopped a program. `address` is always where the
+//  offending instruction was fetched from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionError {
+    UnknownOpcode { address: u16, error: DecodeError },
+    //  0NNN other than 0000: a machine language routine of the host
+    MachineCodeRoutine { address: u16, routine: u16 },
+    StackOverflow { address: u16 },
+    StackUnderflow { address: u16 },
+    //  An instruction built by hand naming a register past VF
+    InvalidRegister { address: u16, register: u8 },
+    //  The program counter left memory, or stopped one byte short of its end
+    ProgramCounterOutOfBounds { address: u16, program_counter: usize },
+    //  `length` bytes from I do not fit in memory
+    MemoryOutOfBounds { address: u16, pointer: u16, length: usize },
+    //  Strict mode: a write to `target`, which programs should leave alone
+    ProtectedWrite { address: u16, target: u16, region: Region },
+    //  Strict mode: the program counter got to memory that is not code
+    ExecutedData { address: u16, region: Region },
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ExecutionError::*;
+
+        match *self {
+            UnknownOpcode { address, error } => write!(f, "{} at {:03X}", error, address),
+            MachineCodeRoutine { address, routine } =>
+                write!(f, "call to machine language routine {:03X} at {:03X}", routine, address),
+            StackOverflow { address } => write!(f, "stack overflow at {:03X}", address),
+            StackUnderflow { address } => write!(f, "return with an empty stack at {:03X}", address),
+            InvalidRegister { address, register } => write!(f, "no register V{} at {:03X}", register, address),
+            ProgramCounterOutOfBounds { address, program_counter } =>
+                write!(f, "program counter {:#05X} out of memory after {:03X}", program_counter, address),
+            MemoryOutOfBounds { address, pointer, length } =>
+                write!(f, "{} bytes at I = {:#05X} out of memory at {:03X}", length, pointer, address),
+            ProtectedWrite { address, target, region } =>
+                write!(f, "write to the {} at {:03X} from {:03X}", region, target, address),
+            ExecutedData { address, region } => write!(f, "execution of the {} at {:03X}", region, address),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
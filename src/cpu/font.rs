@@ -0,0 +1,221 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+//  Bytes per glyph of the hexadecimal font and of the SCHIP big digits
+const GLYPH_SIZE: usize = 5;
+const BIG_GLYPH_SIZE: usize = 10;
+const SMALL_FONT_SIZE: usize = 16 * GLYPH_SIZE;
+const BIG_FONT_SIZE: usize = 10 * BIG_GLYPH_SIZE;
+
+//  Where this interpreter has always put the font, right after the jump at 0x000
+pub const DEFAULT_FONT_ADDRESS: u16 = 0x002;
+//  The font has to stay out of the program area
+const INTERPRETER_AREA_END: usize = 0x200;
+
+//  The hexadecimal fonts of the interpreters people wrote programs for. The
+//  glyphs differ enough that some programs draw text or numbers with them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontSet {
+    //  The font most modern interpreters and Octo use
+    Standard,
+    CosmacVip,
+    Dream6800,
+    Eti660,
+    //  The standard font followed by SCHIP's 8x10 digits
+    SuperChip,
+}
+
+const FONT_SET_IDS: [(&str, FontSet); 5] = [
+    ("standard", FontSet::Standard),
+    ("vip", FontSet::CosmacVip),
+    ("dream6800", FontSet::Dream6800),
+    ("eti660", FontSet::Eti660),
+    ("schip", FontSet::SuperChip),
+];
+
+const STANDARD: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+const COSMAC_VIP: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x60, 0x20, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0xA0, 0xA0, 0xF0, 0x20, 0x20,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x10, 0x10, 0x10,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xF0, 0x50, 0x70, 0x50, 0xF0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xF0, 0x50, 0x50, 0x50, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+const DREAM_6800: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0,
+    0x40, 0x40, 0x40, 0x40, 0x40,
+    0xE0, 0x20, 0xE0, 0x80, 0xE0,
+    0xE0, 0x20, 0xE0, 0x20, 0xE0,
+    0x80, 0xA0, 0xA0, 0xE0, 0x20,
+    0xE0, 0x80, 0xE0, 0x20, 0xE0,
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0,
+    0xE0, 0x20, 0x20, 0x20, 0x20,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0,
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0,
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0,
+    0xE0, 0x80, 0x80, 0x80, 0xE0,
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0,
+    0xE0, 0x80, 0xE0, 0x80, 0xE0,
+    0xE0, 0x80, 0xC0, 0x80, 0x80,
+];
+
+const ETI_660: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0,
+    0x20, 0x20, 0x20, 0x20, 0x20,
+    0xE0, 0x20, 0xE0, 0x80, 0xE0,
+    0xE0, 0x20, 0xE0, 0x20, 0xE0,
+    0xA0, 0xA0, 0xE0, 0x20, 0x20,
+    0xE0, 0x80, 0xE0, 0x20, 0xE0,
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0,
+    0xE0, 0x20, 0x20, 0x20, 0x20,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0,
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0,
+    0x80, 0x80, 0xE0, 0xA0, 0xE0,
+    0xE0, 0x80, 0x80, 0x80, 0xE0,
+    0x20, 0x20, 0xE0, 0xA0, 0xE0,
+    0xE0, 0x80, 0xE0, 0x80, 0xE0,
+    0xE0, 0x80, 0xC0, 0x80, 0x80,
+];
+
+//  SCHIP 1.1's digits 0-9, eight pixels wide and ten high
+const SUPER_CHIP_BIG: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C,
+];
+
+impl FontSet {
+    pub fn id(&self) -> &'static str {
+        FONT_SET_IDS.iter()
+            .find(|(_, set)| set == self)
+            .map(|&(id, _)| id)
+            .unwrap()
+    }
+
+    pub fn font(&self) -> Font {
+        let (small, big): (&[u8], &[u8]) = match self {
+            FontSet::Standard => (&STANDARD, &[]),
+            FontSet::CosmacVip => (&COSMAC_VIP, &[]),
+            FontSet::Dream6800 => (&DREAM_6800, &[]),
+            FontSet::Eti660 => (&ETI_660, &[]),
+            FontSet::SuperChip => (&STANDARD, &SUPER_CHIP_BIG),
+        };
+        Font { glyphs: [small, big].concat(), address: DEFAULT_FONT_ADDRESS }
+    }
+}
+
+impl FromStr for FontSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FONT_SET_IDS.iter()
+            .find(|(id, _)| *id == s)
+            .map(|&(_, set)| set)
+            .ok_or_else(|| format!("unknown font set '{}'", s))
+    }
+}
+
+//  The glyphs FX29 points I at and where in the interpreter area they are.
+//  The 16 hexadecimal glyphs of five bytes come first; the big digits,
+//  when there are any, follow them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Font {
+    glyphs: Vec<u8>,
+    address: u16,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        FontSet::Standard.font()
+    }
+}
+
+impl Font {
+    //  Either the 80 bytes of a hexadecimal font or those followed by the
+    //  100 bytes of the big digits
+    pub fn from_bytes(glyphs: &[u8]) -> Result<Font, String> {
+        if glyphs.len() != SMALL_FONT_SIZE && glyphs.len() != SMALL_FONT_SIZE + BIG_FONT_SIZE {
+            return Err(format!("a font has {} or {} bytes, not {}",
+                               SMALL_FONT_SIZE, SMALL_FONT_SIZE + BIG_FONT_SIZE, glyphs.len()));
+        }
+        Ok(Font { glyphs: glyphs.to_vec(), address: DEFAULT_FONT_ADDRESS })
+    }
+
+    pub fn load(path: &Path) -> Result<Font, String> {
+        let glyphs = fs::read(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Font::from_bytes(&glyphs).map_err(|e| format!("invalid font in {}: {}", path.display(), e))
+    }
+
+    //  Moves the font, which has to fit between the jump at 0x000 and the
+    //  program; many interpreters use 0x050
+    pub fn at(self, address: u16) -> Result<Font, String> {
+        let font = Font { address, ..self };
+        if (address as usize) < DEFAULT_FONT_ADDRESS as usize || font.range().end > INTERPRETER_AREA_END {
+            return Err(format!("a font of {} bytes does not fit at {:03X}", font.glyphs.len(), address));
+        }
+        Ok(font)
+    }
+
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    pub fn glyphs(&self) -> &[u8] {
+        &self.glyphs
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.address as usize..self.address as usize + self.glyphs.len()
+    }
+
+    pub fn has_big_digits(&self) -> bool {
+        self.glyphs.len() > SMALL_FONT_SIZE
+    }
+
+    //  Where the glyph of the hexadecimal digit `character` is
+    pub fn character_address(&self, character: u8) -> u16 {
+        self.address + GLYPH_SIZE as u16 * (character & 0x0F) as u16
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::font::{Font, FontSet};
+#[cfg(test)]
+use crate::cpu::memory_map::Region;
+
+#[test]
+fn font_sets_are_named_like_on_the_command_line(){
+    for set in [FontSet::Standard, FontSet::CosmacVip, FontSet::Dream6800, FontSet::Eti660, FontSet::SuperChip] {
+        assert_eq!(set.id().parse::<FontSet>(), Ok(set));
+    }
+    assert!("chip48".parse::<FontSet>().is_err());
+}
+
+#[test]
+fn only_the_super_chip_set_has_big_digits(){
+    assert!(FontSet::SuperChip.font().has_big_digits());
+    assert_eq!(FontSet::SuperChip.font().glyphs().len(), 180);
+    assert!(!FontSet::CosmacVip.font().has_big_digits());
+    assert_eq!(FontSet::CosmacVip.font().glyphs().len(), 80);
+}
+
+#[test]
+fn fonts_from_bytes_need_one_or_both_sizes(){
+    assert!(Font::from_bytes(&[0xF0; 80]).is_ok());
+    assert!(Font::from_bytes(&[0xF0; 180]).is_ok());
+    assert!(Font::from_bytes(&[0xF0; 81]).is_err());
+}
+
+#[test]
+fn fonts_must_fit_in_the_interpreter_area(){
+    assert_eq!(Font::default().at(0x050).unwrap().range(), 0x050..0x0A0);
+    assert!(Font::default().at(0x1B0).is_ok());
+    assert!(Font::default().at(0x1B1).is_err());
+    assert!(FontSet::SuperChip.font().at(0x180).is_err());
+    //  The jump to the program is at 0x000
+    assert!(Font::default().at(0x000).is_err());
+}
+
+#[test]
+fn fx29_points_at_the_font_wherever_it_is(){
+    let font = FontSet::CosmacVip.font().at(0x050).unwrap();
+    //  V0 = 0x1B; I = glyph of B
+    let mut cpu = CPU::builder().font(font.clone()).rom(&[0x60, 0x1B, 0xF0, 0x29]).build().unwrap();
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.pointer_register(), 0x050 + 5 * 0xB);
+    assert_eq!(cpu.memory()[0x050..0x0A0], font.glyphs()[..]);
+    assert_eq!(cpu.memory()[0x087..0x08C], [0xF0, 0x50, 0x70, 0x50, 0xF0]);
+}
+
+#[test]
+fn moving_the_font_clears_its_old_place_and_moves_its_region(){
+    let mut cpu = CPU::builder().font(Font::default().at(0x100).unwrap()).build().unwrap();
+    assert!(cpu.memory()[0x002..0x052].iter().all(|&byte| byte == 0));
+    assert_eq!(cpu.memory()[0..2], [0x12, 0x00]);
+    assert_eq!(cpu.memory_map().region_at(0x100), Region::Font);
+    assert_eq!(cpu.memory_map().region_at(0x002), Region::Interpreter);
+
+    cpu.load_rom(&[0x00, 0x00]).unwrap();
+    assert_eq!(cpu.font().address(), 0x100);
+    assert_eq!(cpu.memory()[0x100..0x150], Font::default().glyphs()[..]);
+}
@@ -0,0 +1,266 @@
+use std::fmt;
+
+//  The CHIP-8 instruction set. Register operands are register indices;
+//  `value` and `mask` are byte constants, `address` a 12-bit address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    //  0000: stops this interpreter. It is no routine anyone would call, and
+    //  memory past the end of a program is zero, so programs that run off
+    //  their end stop here too.
+    Halt,
+    //  0NNN: a machine language routine of the host, see MachineCodePolicy
+    System { address: u16 },
+    //  00E0
+    ClearScreen,
+    //  00EE
+    Return,
+    //  1NNN
+    Jump { address: u16 },
+    //  2NNN
+    Call { address: u16 },
+    //  3XKK
+    SkipIfEqual { x: u8, value: u8 },
+    //  4XKK
+    SkipIfNotEqual { x: u8, value: u8 },
+    //  5XY0
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    //  6XKK
+    Load { x: u8, value: u8 },
+    //  7XKK
+    AddConstant { x: u8, value: u8 },
+    //  8XY0
+    Copy { x: u8, y: u8 },
+    //  8XY1
+    Or { x: u8, y: u8 },
+    //  8XY2
+    And { x: u8, y: u8 },
+    //  8XY3
+    Xor { x: u8, y: u8 },
+    //  8XY4
+    Add { x: u8, y: u8 },
+    //  8XY5
+    Sub { x: u8, y: u8 },
+    //  8XY6
+    ShiftRight { x: u8, y: u8 },
+    //  8XY7
+    SubReversed { x: u8, y: u8 },
+    //  8XYE
+    ShiftLeft { x: u8, y: u8 },
+    //  9XY0
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    //  ANNN
+    SetPointer { address: u16 },
+    //  BNNN
+    JumpWithOffset { address: u16 },
+    //  CXKK
+    Random { x: u8, mask: u8 },
+    //  DXYN
+    Draw { x: u8, y: u8, n: u8 },
+    //  EX9E
+    SkipIfKey { x: u8 },
+    //  EXA1
+    SkipIfNotKey { x: u8 },
+    //  FX07
+    ReadDelayTimer { x: u8 },
+    //  FX0A
+    WaitForKey { x: u8 },
+    //  FX15
+    SetDelayTimer { x: u8 },
+    //  FX18
+    SetSoundTimer { x: u8 },
+    //  FX1E
+    AddToPointer { x: u8 },
+    //  FX29
+    FontCharacter { x: u8 },
+    //  FX33
+    StoreBcd { x: u8 },
+    //  FX55
+    StoreRegisters { x: u8 },
+    //  FX65
+    LoadRegisters { x: u8 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    pub op_code: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.op_code)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn decode(op_code: u16) -> Result<Instruction, DecodeError> {
+    use Instruction::*;
+
+    let x = ((op_code & 0x0F00) >> 8) as u8;
+    let y = ((op_code & 0x00F0) >> 4) as u8;
+    let n = (op_code & 0x000F) as u8;
+    let value = (op_code & 0x00FF) as u8;
+    let address = op_code & 0x0FFF;
+
+    let instruction = match (op_code >> 12, n) {
+        (0x0, _) => match op_code {
+            0x0000 => Halt,
+            0x00E0 => ClearScreen,
+            0x00EE => Return,
+            _ => System { address },
+        },
+        (0x1, _) => Jump { address },
+        (0x2, _) => Call { address },
+        (0x3, _) => SkipIfEqual { x, value },
+        (0x4, _) => SkipIfNotEqual { x, value },
+        (0x5, 0x0) => SkipIfRegistersEqual { x, y },
+        (0x6, _) => Load { x, value },
+        (0x7, _) => AddConstant { x, value },
+        (0x8, 0x0) => Copy { x, y },
+        (0x8, 0x1) => Or { x, y },
+        (0x8, 0x2) => And { x, y },
+        (0x8, 0x3) => Xor { x, y },
+        (0x8, 0x4) => Add { x, y },
+        (0x8, 0x5) => Sub { x, y },
+        (0x8, 0x6) => ShiftRight { x, y },
+        (0x8, 0x7) => SubReversed { x, y },
+        (0x8, 0xE) => ShiftLeft { x, y },
+        (0x9, 0x0) => SkipIfRegistersNotEqual { x, y },
+        (0xA, _) => SetPointer { address },
+        (0xB, _) => JumpWithOffset { address },
+        (0xC, _) => Random { x, mask: value },
+        (0xD, _) => Draw { x, y, n },
+        (0xE, _) => match value {
+            0x9E => SkipIfKey { x },
+            0xA1 => SkipIfNotKey { x },
+            _ => return Err(DecodeError { op_code }),
+        },
+        (0xF, _) => match value {
+            0x07 => ReadDelayTimer { x },
+            0x0A => WaitForKey { x },
+            0x15 => SetDelayTimer { x },
+            0x18 => SetSoundTimer { x },
+            0x1E => AddToPointer { x },
+            0x29 => FontCharacter { x },
+            0x33 => StoreBcd { x },
+            0x55 => StoreRegisters { x },
+            0x65 => LoadRegisters { x },
+            _ => return Err(DecodeError { op_code }),
+        },
+        _ => return Err(DecodeError { op_code }),
+    };
+    Ok(instruction)
+}
+
+impl Instruction {
+    //  The opcode this instruction decodes from
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+
+        let xkk = |prefix: u16, x: u8, value: u8| prefix << 12 | (x as u16) << 8 | value as u16;
+        let xyn = |prefix: u16, x: u8, y: u8, n: u8| prefix << 12 | (x as u16) << 8 | (y as u16) << 4 | n as u16;
+        match *self {
+            Halt => 0x0000,
+            System { address } => address & 0x0FFF,
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            Jump { address } => 0x1000 | address & 0x0FFF,
+            Call { address } => 0x2000 | address & 0x0FFF,
+            SkipIfEqual { x, value } => xkk(0x3, x, value),
+            SkipIfNotEqual { x, value } => xkk(0x4, x, value),
+            SkipIfRegistersEqual { x, y } => xyn(0x5, x, y, 0x0),
+            Load { x, value } => xkk(0x6, x, value),
+            AddConstant { x, value } => xkk(0x7, x, value),
+            Copy { x, y } => xyn(0x8, x, y, 0x0),
+            Or { x, y } => xyn(0x8, x, y, 0x1),
+            And { x, y } => xyn(0x8, x, y, 0x2),
+            Xor { x, y } => xyn(0x8, x, y, 0x3),
+            Add { x, y } => xyn(0x8, x, y, 0x4),
+            Sub { x, y } => xyn(0x8, x, y, 0x5),
+            ShiftRight { x, y } => xyn(0x8, x, y, 0x6),
+            SubReversed { x, y } => xyn(0x8, x, y, 0x7),
+            ShiftLeft { x, y } => xyn(0x8, x, y, 0xE),
+            SkipIfRegistersNotEqual { x, y } => xyn(0x9, x, y, 0x0),
+            SetPointer { address } => 0xA000 | address & 0x0FFF,
+            JumpWithOffset { address } => 0xB000 | address & 0x0FFF,
+            Random { x, mask } => xkk(0xC, x, mask),
+            Draw { x, y, n } => xyn(0xD, x, y, n),
+            SkipIfKey { x } => xkk(0xE, x, 0x9E),
+            SkipIfNotKey { x } => xkk(0xE, x, 0xA1),
+            ReadDelayTimer { x } => xkk(0xF, x, 0x07),
+            WaitForKey { x } => xkk(0xF, x, 0x0A),
+            SetDelayTimer { x } => xkk(0xF, x, 0x15),
+            SetSoundTimer { x } => xkk(0xF, x, 0x18),
+            AddToPointer { x } => xkk(0xF, x, 0x1E),
+            FontCharacter { x } => xkk(0xF, x, 0x29),
+            StoreBcd { x } => xkk(0xF, x, 0x33),
+            StoreRegisters { x } => xkk(0xF, x, 0x55),
+            LoadRegisters { x } => xkk(0xF, x, 0x65),
+        }
+    }
+
+    //  Cowgod's mnemonic, which groups instructions into classes
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            Halt => "HALT",
+            System { .. } => "SYS",
+            ClearScreen => "CLS",
+            Return => "RET",
+            Jump { .. } | JumpWithOffset { .. } => "JP",
+            Call { .. } => "CALL",
+            SkipIfEqual { .. } | SkipIfRegistersEqual { .. } => "SE",
+            SkipIfNotEqual { .. } | SkipIfRegistersNotEqual { .. } => "SNE",
+            Add { .. } | AddConstant { .. } | AddToPointer { .. } => "ADD",
+            Or { .. } => "OR",
+            And { .. } => "AND",
+            Xor { .. } => "XOR",
+            Sub { .. } => "SUB",
+            ShiftRight { .. } => "SHR",
+            SubReversed { .. } => "SUBN",
+            ShiftLeft { .. } => "SHL",
+            Random { .. } => "RND",
+            Draw { .. } => "DRW",
+            SkipIfKey { .. } => "SKP",
+            SkipIfNotKey { .. } => "SKNP",
+            Load { .. } | Copy { .. } | SetPointer { .. } | ReadDelayTimer { .. } | WaitForKey { .. }
+            | SetDelayTimer { .. } | SetSoundTimer { .. } | FontCharacter { .. } | StoreBcd { .. }
+            | StoreRegisters { .. } | LoadRegisters { .. } => "LD",
+        }
+    }
+}
+
+//  Cowgod's syntax
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        let mnemonic = self.mnemonic();
+        match *self {
+            Halt | ClearScreen | Return => write!(f, "{}", mnemonic),
+            System { address } | Jump { address } | Call { address } =>
+                write!(f, "{} 0x{:03X}", mnemonic, address),
+            SetPointer { address } => write!(f, "LD I, 0x{:03X}", address),
+            JumpWithOffset { address } => write!(f, "JP V0, 0x{:03X}", address),
+            SkipIfEqual { x, value } | SkipIfNotEqual { x, value } | Load { x, value }
+            | AddConstant { x, value } | Random { x, mask: value } =>
+                write!(f, "{} V{:X}, 0x{:02X}", mnemonic, x, value),
+            SkipIfRegistersEqual { x, y } | SkipIfRegistersNotEqual { x, y } | Copy { x, y }
+            | Or { x, y } | And { x, y } | Xor { x, y } | Add { x, y } | Sub { x, y }
+            | ShiftRight { x, y } | SubReversed { x, y } | ShiftLeft { x, y } =>
+                write!(f, "{} V{:X}, V{:X}", mnemonic, x, y),
+            Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipIfKey { x } | SkipIfNotKey { x } => write!(f, "{} V{:X}", mnemonic, x),
+            ReadDelayTimer { x } => write!(f, "LD V{:X}, DT", x),
+            WaitForKey { x } => write!(f, "LD V{:X}, K", x),
+            SetDelayTimer { x } => write!(f, "LD DT, V{:X}", x),
+            SetSoundTimer { x } => write!(f, "LD ST, V{:X}", x),
+            AddToPointer { x } => write!(f, "ADD I, V{:X}", x),
+            FontCharacter { x } => write!(f, "LD F, V{:X}", x),
+            StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+            LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
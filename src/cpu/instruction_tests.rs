@@ -0,0 +1,74 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::disassembler::disassemble;
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::instruction::{decode, DecodeError, Instruction};
+
+#[test]
+fn decodes_typed_operands(){
+    assert_eq!(decode(0xD12F), Ok(Instruction::Draw { x: 1, y: 2, n: 0xF }));
+    assert_eq!(decode(0xC3A5), Ok(Instruction::Random { x: 3, mask: 0xA5 }));
+    assert_eq!(decode(0xB234), Ok(Instruction::JumpWithOffset { address: 0x234 }));
+    assert_eq!(decode(0x0000), Ok(Instruction::Halt));
+    assert_eq!(decode(0x0123), Ok(Instruction::System { address: 0x123 }));
+    assert_eq!(decode(0xFE65), Ok(Instruction::LoadRegisters { x: 0xE }));
+}
+
+#[test]
+fn rejects_unknown_opcodes(){
+    for op_code in [0x5121, 0x812F, 0x9AB1, 0xE19F, 0xF0FF, 0xF156] {
+        assert_eq!(decode(op_code), Err(DecodeError { op_code }));
+    }
+    assert_eq!(DecodeError { op_code: 0xF0FF }.to_string(), "unknown opcode F0FF");
+}
+
+#[test]
+fn every_instruction_encodes_back_to_its_opcode(){
+    let mut decoded = 0;
+    for op_code in 0..=0xFFFFu16 {
+        if let Ok(instruction) = decode(op_code) {
+            assert_eq!(instruction.encode(), op_code, "{:?}", instruction);
+            decoded += 1;
+        }
+    }
+    //  Everything but the gaps in the 5, 8, 9, E and F groups
+    assert_eq!(decoded, 0x10000 - 15 * 0x100 - 7 * 0x100 - 15 * 0x100 - 254 * 0x10 - 247 * 0x10);
+}
+
+#[test]
+fn disassembles_through_the_instruction(){
+    assert_eq!(disassemble(0x8AB6), "SHR VA, VB");
+    assert_eq!(disassemble(0xF218), "LD ST, V2");
+    assert_eq!(disassemble(0xA2F0), "LD I, 0x2F0");
+    assert_eq!(disassemble(0x5AB1), "DW 0x5AB1");
+}
+
+#[test]
+fn executes_decoded_instructions(){
+    let mut cpu = CPU::builder().register(0x4, 200).build().unwrap();
+    cpu.execute(Instruction::AddConstant { x: 4, value: 100 }).unwrap();
+    cpu.execute(Instruction::SetSoundTimer { x: 4 }).unwrap();
+    assert_eq!(cpu.registers()[4], 44);
+    assert_eq!(cpu.sound_timer(), 44);
+    cpu.tick_timers();
+    assert_eq!(cpu.sound_timer(), 43);
+    //  Nothing was fetched, so the program counter has not moved
+    assert_eq!(cpu.program_counter(), 0x200);
+}
+
+#[test]
+fn waiting_for_a_key_at_the_start_of_memory_is_an_error(){
+    let mut cpu = CPU::builder().program_counter(0x000).build().unwrap();
+    assert_eq!(cpu.execute(Instruction::WaitForKey { x: 0 }),
+               Err(ExecutionError::ProgramCounterOutOfBounds { address: 0xFFFE, program_counter: 0 }));
+}
+
+#[test]
+fn instructions_naming_missing_registers_are_errors(){
+    let mut cpu = CPU::builder().program_counter(0x202).build().unwrap();
+    assert_eq!(cpu.execute(Instruction::Load { x: 16, value: 1 }),
+               Err(ExecutionError::InvalidRegister { address: 0x200, register: 16 }));
+}
@@ -0,0 +1,24 @@
+use std::str::FromStr;
+
+//  What 0NNN does. On the COSMAC VIP it calls the 1802 machine language
+//  routine at NNN, which no interpreter without an 1802 can run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MachineCodePolicy {
+    //  Does nothing, like most interpreters after the VIP
+    Ignore,
+    //  Stops the program with ExecutionError::MachineCodeRoutine
+    #[default]
+    Error,
+}
+
+impl FromStr for MachineCodePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(MachineCodePolicy::Ignore),
+            "error" => Ok(MachineCodePolicy::Error),
+            _ => Err(format!("unknown machine code policy '{}' (expected ignore or error)", s)),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::machine_code::MachineCodePolicy;
+
+#[cfg(test)]
+fn run(rom: &[u8], policy: MachineCodePolicy) -> CPU {
+    let mut cpu = CPU::builder().rom(rom).machine_code_policy(policy).build().unwrap();
+    let _ = cpu.run_headless();
+    cpu
+}
+
+#[test]
+fn policies_are_named_like_on_the_command_line(){
+    assert_eq!("ignore".parse(), Ok(MachineCodePolicy::Ignore));
+    assert_eq!("error".parse(), Ok(MachineCodePolicy::Error));
+    assert!("run".parse::<MachineCodePolicy>().is_err());
+}
+
+#[test]
+fn machine_code_is_an_error_by_default(){
+    let cpu = run(&[0x62, 0x01, 0x03, 0x00, 0x63, 0x01, 0x00, 0x00], MachineCodePolicy::default());
+    assert_eq!(cpu.error(), Some(ExecutionError::MachineCodeRoutine { address: 0x202, routine: 0x300 }));
+    assert_eq!(cpu.registers()[3], 0);
+}
+
+#[test]
+fn ignored_machine_code_does_nothing(){
+    let cpu = run(&[0x03, 0x00, 0x63, 0x01, 0x00, 0x00], MachineCodePolicy::Ignore);
+    assert_eq!(cpu.error(), None);
+    assert_eq!(cpu.registers()[3], 1);
+}
+
+#[test]
+fn zero_halts_whatever_the_policy(){
+    for policy in [MachineCodePolicy::Ignore, MachineCodePolicy::Error] {
+        let cpu = run(&[0x00, 0x00, 0x63, 0x01], policy);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.error(), None);
+        assert_eq!(cpu.registers()[3], 0);
+    }
+}
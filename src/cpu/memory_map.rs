@@ -0,0 +1,103 @@
+use std::fmt;
+use std::ops::Range;
+use crate::cpu::font::Font;
+
+//  The jump to the program at 0x000, which 0NNN and 1NNN may reach
+const BOOT_JUMP: Range<usize> = 0x000..0x002;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    //  Reserved for the interpreter; its code lived here on the COSMAC VIP
+    Interpreter,
+    Font,
+    Program,
+    //  The COSMAC VIP keeps the return addresses and the display in the
+    //  top of its memory
+    Stack,
+    Display,
+}
+
+impl Region {
+    pub fn is_writable(&self) -> bool {
+        *self == Region::Program
+    }
+
+    pub fn is_executable(&self) -> bool {
+        *self == Region::Program
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Region::Interpreter => "interpreter area",
+            Region::Font => "font",
+            Region::Program => "program area",
+            Region::Stack => "stack",
+            Region::Display => "display memory",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+//  What each part of the 4 KB of memory is for, so that strict mode can
+//  tell a program writing over the font or running into its sprite data
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryMap {
+    regions: Vec<(Range<usize>, Region)>,
+    font: Range<usize>,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        MemoryMap::standard()
+    }
+}
+
+impl MemoryMap {
+    //  The interpreter area up to 0x200, and the program everywhere above
+    pub fn standard() -> MemoryMap {
+        MemoryMap {
+            regions: vec![
+                (0x000..0x200, Region::Interpreter),
+                (0x200..0x1000, Region::Program),
+            ],
+            font: Font::default().range(),
+        }
+    }
+
+    //  A 4 KB COSMAC VIP, which keeps its stack, its variables and the
+    //  display buffer above the program
+    pub fn cosmac_vip() -> MemoryMap {
+        MemoryMap {
+            regions: vec![
+                (0x000..0x200, Region::Interpreter),
+                (0x200..0xEA0, Region::Program),
+                (0xEA0..0xED0, Region::Stack),
+                (0xED0..0xF00, Region::Interpreter),
+                (0xF00..0x1000, Region::Display),
+            ],
+            font: Font::default().range(),
+        }
+    }
+
+    //  The same map with the font somewhere else
+    pub fn with_font(self, font: Range<usize>) -> MemoryMap {
+        MemoryMap { font, ..self }
+    }
+
+    pub fn region_at(&self, address: usize) -> Region {
+        if self.font.contains(&address) {
+            return Region::Font;
+        }
+        self.regions.iter()
+            .find(|(range, _)| range.contains(&address))
+            .map_or(Region::Interpreter, |&(_, region)| region)
+    }
+
+    //  Programs only, apart from the jump to the program in the interpreter
+    //  area; the rest of it held the interpreter's own code
+    pub fn is_executable(&self, address: usize) -> bool {
+        BOOT_JUMP.contains(&address) || self.region_at(address).is_executable()
+    }
+}
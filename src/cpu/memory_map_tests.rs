@@ -0,0 +1,100 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::memory_map::{MemoryMap, Region};
+
+#[cfg(test)]
+fn strict_cpu(rom: &[u8], memory_map: MemoryMap) -> CPU {
+    CPU::builder().rom(rom).memory_map(memory_map).strict(true).build().unwrap()
+}
+
+#[test]
+fn the_standard_map_has_the_font_in_the_interpreter_area(){
+    let map = MemoryMap::standard();
+    assert_eq!(map.region_at(0x000), Region::Interpreter);
+    assert_eq!(map.region_at(0x002), Region::Font);
+    assert_eq!(map.region_at(0x051), Region::Font);
+    assert_eq!(map.region_at(0x052), Region::Interpreter);
+    assert_eq!(map.region_at(0x200), Region::Program);
+    assert_eq!(map.region_at(0xFFF), Region::Program);
+}
+
+#[test]
+fn the_cosmac_vip_map_keeps_the_stack_and_display_at_the_top(){
+    let map = MemoryMap::cosmac_vip();
+    assert_eq!(map.region_at(0xE9F), Region::Program);
+    assert_eq!(map.region_at(0xEA0), Region::Stack);
+    assert_eq!(map.region_at(0xED0), Region::Interpreter);
+    assert_eq!(map.region_at(0xF00), Region::Display);
+    assert_eq!(map.region_at(0xFFF), Region::Display);
+}
+
+#[test]
+fn strict_mode_reports_writes_over_the_font(){
+    //  I = 0x010; V0 = 0x99; store V0 at I
+    let mut cpu = strict_cpu(&[0xA0, 0x10, 0x60, 0x99, 0xF0, 0x55], MemoryMap::standard());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ProtectedWrite { address: 0x204, target: 0x010, region: Region::Font }));
+    assert_ne!(cpu.memory()[0x010], 0x99);
+}
+
+#[test]
+fn strict_mode_checks_every_byte_before_writing(){
+    //  I = 0xE9E; V0..V2 straddle the end of the VIP program area
+    let mut cpu = strict_cpu(&[0xAE, 0x9E, 0xF2, 0x55], MemoryMap::cosmac_vip());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ProtectedWrite { address: 0x202, target: 0xEA0, region: Region::Stack }));
+    assert_eq!(cpu.memory()[0xE9E..0xEA1], [0, 0, 0]);
+}
+
+#[test]
+fn strict_mode_reports_bcd_into_the_vip_display(){
+    let mut cpu = strict_cpu(&[0xAF, 0x00, 0xF0, 0x33], MemoryMap::cosmac_vip());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ProtectedWrite { address: 0x202, target: 0xF00, region: Region::Display }));
+}
+
+#[test]
+fn strict_mode_reports_jumps_into_the_font(){
+    let mut cpu = strict_cpu(&[0x10, 0x0A], MemoryMap::standard());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ExecutedData { address: 0x00A, region: Region::Font }));
+}
+
+#[test]
+fn strict_mode_reports_running_into_the_vip_stack(){
+    let mut cpu = strict_cpu(&[0x1E, 0xA0], MemoryMap::cosmac_vip());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ExecutedData { address: 0xEA0, region: Region::Stack }));
+}
+
+#[test]
+fn strict_mode_reports_running_into_the_interpreter_area(){
+    let mut cpu = strict_cpu(&[0x10, 0x52], MemoryMap::standard());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ExecutedData { address: 0x052, region: Region::Interpreter }));
+    let mut cpu = strict_cpu(&[0x1E, 0xD0], MemoryMap::cosmac_vip());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ExecutedData { address: 0xED0, region: Region::Interpreter }));
+}
+
+#[test]
+fn strict_mode_allows_the_jump_to_the_program(){
+    //  Jumps back to 0x000, which jumps to 0x200 again
+    let mut cpu = strict_cpu(&[0x10, 0x00], MemoryMap::standard());
+    for _ in 0..4 {
+        cpu.step();
+    }
+    assert_eq!(cpu.error(), None);
+    assert_eq!(cpu.program_counter(), 0x200);
+}
+
+#[test]
+fn without_strict_mode_the_font_can_be_overwritten(){
+    let mut cpu = CPU::builder().rom(&[0xA0, 0x10, 0x60, 0x99, 0xF0, 0x55, 0x00, 0x00]).build().unwrap();
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.memory()[0x010], 0x99);
+}
+
+#[test]
+fn strict_mode_survives_loading_a_rom(){
+    let mut cpu = strict_cpu(&[], MemoryMap::cosmac_vip());
+    cpu.load_rom(&[0x1F, 0x00]).unwrap();
+    assert_eq!(cpu.memory_map(), &MemoryMap::cosmac_vip());
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::ExecutedData { address: 0xF00, region: Region::Display }));
+}
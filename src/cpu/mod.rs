@@ -1,2 +1,30 @@
+#[allow(clippy::module_inception)]
 pub mod cpu;
+pub mod blocks;
+pub mod coverage;
+pub mod disassembler;
+pub mod display;
+pub mod error;
+pub mod font;
+pub mod instruction;
+pub mod machine_code;
+pub mod memory_map;
+pub mod profiler;
+pub mod quirks;
+pub mod stack;
+pub mod timing;
+pub mod trace;
+pub mod tracediff;
+mod blocks_tests;
+mod coverage_tests;
 mod cpu_tests;
+mod display_tests;
+mod font_tests;
+mod instruction_tests;
+mod machine_code_tests;
+mod memory_map_tests;
+mod opcode_tests;
+mod profiler_tests;
+mod property_tests;
+mod stack_tests;
+mod trace_tests;
@@ -0,0 +1,345 @@
+#[cfg(test)]
+use crate::cpu::cpu::{CPU, CPUBuilder};
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::quirks::Quirks;
+
+#[cfg(test)]
+fn machine() -> CPUBuilder {
+    CPU::builder()
+}
+
+//  Executes `op_code` from 0x200 in the state set up by `builder`
+#[cfg(test)]
+fn execute(op_code: u16, builder: CPUBuilder) -> CPU {
+    let mut cpu = builder.rom(&op_code.to_be_bytes()).build().unwrap();
+    cpu.step();
+    cpu
+}
+
+#[test]
+fn builder_sets_up_the_whole_state(){
+    let cpu = machine()
+        .register(0x3, 7)
+        .program_counter(0x300)
+        .pointer_register(0x400)
+        .stack(&[0x202, 0x20A])
+        .delay_timer(9)
+        .memory_at(0x400, &[1, 2, 3])
+        .key(0xB, true)
+        .build()
+        .unwrap();
+    assert_eq!(cpu.registers()[3], 7);
+    assert_eq!(cpu.program_counter(), 0x300);
+    assert_eq!(cpu.pointer_register(), 0x400);
+    assert_eq!(cpu.stack(), &[0x202, 0x20A]);
+    assert_eq!(cpu.stack_pointer(), 2);
+    assert_eq!(cpu.delay_timer(), 9);
+    assert_eq!(cpu.memory_range(0x400, 4), &[1, 2, 3, 0]);
+    assert!(cpu.keypad()[0xB]);
+    assert_eq!(cpu.memory_range(0xFFE, 4).len(), 2);
+}
+
+#[test]
+fn builder_rejects_impossible_states(){
+    assert!(machine().register(16, 0).build().is_err());
+    assert!(machine().program_counter(0xFFF).build().is_err());
+    assert!(machine().memory_at(0xFFE, &[0, 0, 0]).build().is_err());
+    assert!(machine().stack(&[0x200; 17]).build().is_err());
+}
+
+#[test]
+fn opcode_00e0_clears_the_display(){
+    let mut cpu = machine().rom(&[0xD0, 0x05, 0x00, 0xE0]).build().unwrap();
+    cpu.step();
+    assert!(cpu.framebuffer().pixels().any(|pixel| pixel));
+    cpu.step();
+    assert!(cpu.framebuffer().pixels().all(|pixel| !pixel));
+}
+
+#[test]
+fn opcode_00ee_returns_to_the_top_of_the_stack(){
+    let cpu = execute(0x00EE, machine().stack(&[0x206, 0x310]));
+    assert_eq!(cpu.program_counter(), 0x310);
+    assert_eq!(cpu.stack(), &[0x206]);
+}
+
+#[test]
+fn opcode_1nnn_jumps(){
+    let cpu = execute(0x1ABC, machine());
+    assert_eq!(cpu.program_counter(), 0xABC);
+    assert!(cpu.stack().is_empty());
+}
+
+#[test]
+fn opcode_2nnn_pushes_the_return_address(){
+    let mut cpu = machine().program_counter(0x240).memory_at(0x240, &[0x2A, 0xBC]).build().unwrap();
+    cpu.step();
+    assert_eq!(cpu.program_counter(), 0xABC);
+    assert_eq!(cpu.stack(), &[0x242]);
+}
+
+#[test]
+fn opcodes_3xkk_and_4xkk_compare_with_a_constant(){
+    let state = || machine().register(0x5, 0x42);
+    assert_eq!(execute(0x3542, state()).program_counter(), 0x204);
+    assert_eq!(execute(0x3543, state()).program_counter(), 0x202);
+    assert_eq!(execute(0x4542, state()).program_counter(), 0x202);
+    assert_eq!(execute(0x4543, state()).program_counter(), 0x204);
+}
+
+#[test]
+fn opcodes_5xy0_and_9xy0_compare_registers(){
+    let state = || machine().register(0x1, 3).register(0x2, 3).register(0x3, 4);
+    assert_eq!(execute(0x5120, state()).program_counter(), 0x204);
+    assert_eq!(execute(0x5130, state()).program_counter(), 0x202);
+    assert_eq!(execute(0x9120, state()).program_counter(), 0x202);
+    assert_eq!(execute(0x9130, state()).program_counter(), 0x204);
+}
+
+#[test]
+fn opcodes_6xkk_and_7xkk_load_and_add_constants(){
+    assert_eq!(execute(0x6A99, machine()).registers()[0xA], 0x99);
+    let cpu = execute(0x7A02, machine().register(0xA, 0xFF).register(0xF, 7));
+    //  The addition wraps and leaves VF alone
+    assert_eq!(cpu.registers()[0xA], 0x01);
+    assert_eq!(cpu.registers()[0xF], 7);
+}
+
+#[test]
+fn opcodes_8xy0_to_8xy3_move_and_combine_registers(){
+    let state = || machine().register(0x1, 0b1100).register(0x2, 0b1010);
+    assert_eq!(execute(0x8120, state()).registers()[1], 0b1010);
+    assert_eq!(execute(0x8121, state()).registers()[1], 0b1110);
+    assert_eq!(execute(0x8122, state()).registers()[1], 0b1000);
+    assert_eq!(execute(0x8123, state()).registers()[1], 0b0110);
+}
+
+#[test]
+fn opcode_8xy4_sets_the_carry(){
+    let cpu = execute(0x8124, machine().register(0x1, 200).register(0x2, 100));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (44, 1));
+    let cpu = execute(0x8124, machine().register(0x1, 20).register(0x2, 100));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (120, 0));
+}
+
+#[test]
+fn opcode_8xy4_writes_the_carry_last(){
+    let cpu = execute(0x8F14, machine().register(0xF, 200).register(0x1, 100));
+    assert_eq!(cpu.registers()[0xF], 1);
+    let cpu = execute(0x8F14, machine().register(0xF, 20).register(0x1, 100));
+    assert_eq!(cpu.registers()[0xF], 0);
+    //  VF as the source is read before the flag is written
+    let cpu = execute(0x81F4, machine().register(0x1, 20).register(0xF, 100));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (120, 0));
+}
+
+#[test]
+fn opcode_8xy5_sets_vf_when_there_is_no_borrow(){
+    let cpu = execute(0x8125, machine().register(0x1, 100).register(0x2, 30));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (70, 1));
+    let cpu = execute(0x8125, machine().register(0x1, 30).register(0x2, 100));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (186, 0));
+    //  Equal operands do not borrow
+    let cpu = execute(0x8125, machine().register(0x1, 30).register(0x2, 30));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (0, 1));
+}
+
+#[test]
+fn opcode_8xy5_writes_the_flag_last(){
+    let cpu = execute(0x8F15, machine().register(0xF, 100).register(0x1, 30));
+    assert_eq!(cpu.registers()[0xF], 1);
+    let cpu = execute(0x8F15, machine().register(0xF, 30).register(0x1, 100));
+    assert_eq!(cpu.registers()[0xF], 0);
+    let cpu = execute(0x81F5, machine().register(0x1, 100).register(0xF, 30));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (70, 1));
+}
+
+#[test]
+fn opcode_8xy7_sets_vf_when_there_is_no_borrow(){
+    let cpu = execute(0x8127, machine().register(0x1, 30).register(0x2, 100));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (70, 1));
+    let cpu = execute(0x8127, machine().register(0x1, 100).register(0x2, 30));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (186, 0));
+}
+
+#[test]
+fn opcode_8xy7_writes_the_flag_last(){
+    let cpu = execute(0x8F17, machine().register(0xF, 30).register(0x1, 100));
+    assert_eq!(cpu.registers()[0xF], 1);
+    let cpu = execute(0x8F17, machine().register(0xF, 100).register(0x1, 30));
+    assert_eq!(cpu.registers()[0xF], 0);
+    let cpu = execute(0x81F7, machine().register(0x1, 100).register(0xF, 30));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (186, 0));
+}
+
+#[test]
+fn opcodes_8xy6_and_8xye_shift_the_bit_out_into_vf(){
+    let state = || machine().register(0x1, 0b1000_0001);
+    let cpu = execute(0x8106, state());
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (0b0100_0000, 1));
+    let cpu = execute(0x810E, state());
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (0b0000_0010, 1));
+    let state = || machine().register(0x1, 0b0100_0010);
+    assert_eq!(execute(0x8106, state()).registers()[0xF], 0);
+    assert_eq!(execute(0x810E, state()).registers()[0xF], 0);
+}
+
+#[test]
+fn opcodes_8xy6_and_8xye_write_the_flag_last(){
+    let state = |value| machine().register(0xF, value);
+    assert_eq!(execute(0x8F06, state(0b0000_0011)).registers()[0xF], 1);
+    assert_eq!(execute(0x8F06, state(0b0000_0010)).registers()[0xF], 0);
+    assert_eq!(execute(0x8F0E, state(0b1100_0000)).registers()[0xF], 1);
+    assert_eq!(execute(0x8F0E, state(0b0100_0000)).registers()[0xF], 0);
+    //  Shifting VY into VX, with VF as VY
+    let quirks = Quirks { shift: false, ..Quirks::default() };
+    let cpu = execute(0x81F6, machine().quirks(quirks).register(0xF, 0b0000_0101));
+    assert_eq!((cpu.registers()[1], cpu.registers()[0xF]), (0b0000_0010, 1));
+}
+
+#[test]
+fn opcodes_annn_and_bnnn_use_addresses(){
+    assert_eq!(execute(0xA123, machine()).pointer_register(), 0x123);
+    let cpu = execute(0xB300, machine().register(0x0, 0x10));
+    assert_eq!(cpu.program_counter(), 0x310);
+}
+
+#[test]
+fn opcode_cxkk_masks_the_random_number(){
+    for _ in 0..32 {
+        let cpu = execute(0xC30F, machine());
+        assert_eq!(cpu.registers()[3] & 0xF0, 0);
+    }
+    assert_eq!(execute(0xC300, machine().register(0x3, 9)).registers()[3], 0);
+}
+
+#[test]
+fn opcode_dxyn_draws_the_sprite_at_i(){
+    let cpu = execute(0xD122, machine()
+        .register(0x1, 60)
+        .register(0x2, 4)
+        .pointer_register(0x300)
+        .memory_at(0x300, &[0b1000_0001, 0b0100_0000]));
+    let (width, _) = cpu.display_size();
+    let lit: Vec<usize> = cpu.framebuffer().pixels().enumerate()
+        .filter(|&(_, pixel)| pixel)
+        .map(|(index, _)| index)
+        .collect();
+    //  The right column of the sprite is clipped at the edge of the screen
+    assert_eq!(lit, vec![4 * width + 60, 5 * width + 61]);
+}
+
+#[test]
+fn opcode_dxyn_sets_vf_when_any_row_collides(){
+    let mut cpu = machine()
+        .pointer_register(0x300)
+        .memory_at(0x300, &[0x80, 0x01])
+        .rom(&[0xD0, 0x01, 0xD0, 0x02])
+        .build().unwrap();
+    cpu.step();
+    assert_eq!(cpu.registers()[0xF], 0);
+    //  Only the first row collides; drawing the second must not clear VF again
+    cpu.step();
+    assert_eq!(cpu.registers()[0xF], 1);
+}
+
+#[test]
+fn opcodes_ex9e_and_exa1_test_the_keypad(){
+    let state = |pressed| machine().register(0x4, 0xC).key(0xC, pressed);
+    assert_eq!(execute(0xE49E, state(true)).program_counter(), 0x204);
+    assert_eq!(execute(0xE49E, state(false)).program_counter(), 0x202);
+    assert_eq!(execute(0xE4A1, state(true)).program_counter(), 0x202);
+    assert_eq!(execute(0xE4A1, state(false)).program_counter(), 0x204);
+}
+
+#[test]
+fn opcodes_fx07_and_fx15_move_the_delay_timer(){
+    assert_eq!(execute(0xF207, machine().delay_timer(33)).registers()[2], 33);
+    //  A longer frame, so the timer does not tick right after the instruction
+    let cpu = execute(0xF215, machine().register(0x2, 44).cycles_per_frame(10));
+    assert_eq!(cpu.delay_timer(), 44);
+}
+
+#[test]
+fn opcode_fx0a_waits_in_place(){
+    let cpu = execute(0xF30A, machine());
+    assert_eq!(cpu.program_counter(), 0x200);
+    assert!(cpu.waiting_for_key());
+}
+
+#[test]
+fn opcodes_fx1e_and_fx29_move_i(){
+    let cpu = execute(0xF51E, machine().register(0x5, 0x10).pointer_register(0x300));
+    assert_eq!(cpu.pointer_register(), 0x310);
+    let cpu = execute(0xF529, machine().register(0x5, 0xA));
+    let glyph = cpu.memory_range(cpu.pointer_register(), 5);
+    assert_eq!(glyph, &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+#[test]
+fn opcode_fx33_stores_decimal_digits(){
+    let cpu = execute(0xF633, machine().register(0x6, 254).pointer_register(0x500));
+    assert_eq!(cpu.memory_range(0x500, 3), &[2, 5, 4]);
+    assert_eq!(cpu.pointer_register(), 0x500);
+}
+
+#[test]
+fn opcodes_fx55_and_fx65_transfer_registers(){
+    let cpu = execute(0xF255, machine()
+        .registers([1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        .pointer_register(0x500));
+    assert_eq!(cpu.memory_range(0x500, 4), &[1, 2, 3, 0]);
+    let cpu = execute(0xF165, machine()
+        .memory_at(0x500, &[7, 8, 9])
+        .pointer_register(0x500));
+    assert_eq!(&cpu.registers()[..3], &[7, 8, 0]);
+}
+
+#[test]
+fn opcode_fx1e_wraps_i_around_the_address_space(){
+    let cpu = execute(0xF11E, machine().pointer_register(0xFF0).register(0x1, 0x20).register(0xF, 7));
+    assert_eq!(cpu.pointer_register(), 0x010);
+    //  VF is left alone without the Amiga quirk
+    assert_eq!(cpu.registers()[0xF], 7);
+}
+
+#[test]
+fn opcode_fx1e_sets_vf_on_overflow_with_the_amiga_quirk(){
+    let quirks = Quirks { pointer_overflow: true, ..Quirks::default() };
+    let state = |i| machine().quirks(quirks).pointer_register(i).register(0x1, 0x20).register(0xF, 7);
+    let cpu = execute(0xF11E, state(0xFF0));
+    assert_eq!((cpu.pointer_register(), cpu.registers()[0xF]), (0x010, 1));
+    let cpu = execute(0xF11E, state(0x100));
+    assert_eq!((cpu.pointer_register(), cpu.registers()[0xF]), (0x120, 0));
+    //  With VF as the operand the flag still ends up in VF
+    let cpu = execute(0xFF1E, machine().quirks(quirks).pointer_register(0xFFF).register(0xF, 1));
+    assert_eq!((cpu.pointer_register(), cpu.registers()[0xF]), (0x000, 1));
+}
+
+#[test]
+fn opcodes_using_i_wrap_around_the_end_of_memory(){
+    let cpu = execute(0xF355, machine().pointer_register(0xFFE).registers([1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]));
+    assert_eq!((cpu.memory()[0xFFE], cpu.memory()[0xFFF]), (1, 2));
+    assert_eq!((cpu.memory()[0x000], cpu.memory()[0x001]), (3, 4));
+
+    let cpu = execute(0xF065, machine().pointer_register(0xFFF).memory_at(0xFFF, &[9]));
+    assert_eq!(cpu.registers()[0], 9);
+
+    let cpu = execute(0xF133, machine().pointer_register(0xFFF).register(0x1, 234));
+    assert_eq!((cpu.memory()[0xFFF], cpu.memory()[0x000], cpu.memory()[0x001]), (2, 3, 4));
+
+    let cpu = execute(0xD012, machine().pointer_register(0xFFF).memory_at(0xFFF, &[0x80]).memory_at(0x000, &[0x40]));
+    assert!(cpu.framebuffer().pixel(0, 0));
+    assert!(cpu.framebuffer().pixel(1, 1));
+}
+
+#[test]
+fn xo_chip_addresses_beyond_memory_fail(){
+    let quirks = Quirks { address_mask: 0xFFFF, ..Quirks::default() };
+    let cpu = execute(0xF11E, machine().quirks(quirks).pointer_register(0xFF0).register(0x1, 0x20));
+    assert_eq!(cpu.pointer_register(), 0x1010);
+    let cpu = execute(0xF065, machine().quirks(quirks).pointer_register(0x1010));
+    assert_eq!(cpu.error(), Some(ExecutionError::MemoryOutOfBounds { address: 0x200, pointer: 0x1010, length: 1 }));
+}
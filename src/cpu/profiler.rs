@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::cpu::disassembler;
+
+//  How many addresses and routines the report lists
+const REPORT_ROWS: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoutineStats {
+    pub calls: u64,
+    //  Instructions executed in the routine itself
+    pub own: u64,
+    //  Instructions executed in the routine and everything it called
+    pub inclusive: u64,
+}
+
+//  Counts what the interpreter executes. Routines are the targets of CALL;
+//  instructions outside any of them belong to the top level (None).
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    instructions: u64,
+    address_counts: Vec<u64>,
+    opcodes: Vec<u16>,
+    classes: BTreeMap<&'static str, u64>,
+    call_stack: Vec<u16>,
+    routines: BTreeMap<Option<u16>, RoutineStats>,
+    key_wait_instructions: u64,
+    key_wait_frames: u64,
+    waited_this_frame: bool,
+    frames: u64,
+    draws_this_frame: u64,
+    draws_per_frame: BTreeMap<u64, u64>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler {
+            instructions: 0,
+            address_counts: vec![0; 0x1000],
+            opcodes: vec![0; 0x1000],
+            classes: BTreeMap::new(),
+            call_stack: Vec::new(),
+            routines: BTreeMap::new(),
+            key_wait_instructions: 0,
+            key_wait_frames: 0,
+            waited_this_frame: false,
+            frames: 0,
+            draws_this_frame: 0,
+            draws_per_frame: BTreeMap::new(),
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    //  Called once `op_code`, fetched from `pc`, has been executed;
+    //  `waiting_for_key` tells whether FX0A is still waiting, and
+    //  `executed` is false when the instruction failed, in which case
+    //  the call stack is left alone.
+    pub fn record(&mut self, pc: u16, op_code: u16, waiting_for_key: bool, executed: bool) {
+        let address = pc as usize & 0x0FFF;
+        self.instructions += 1;
+        self.address_counts[address] += 1;
+        self.opcodes[address] = op_code;
+        *self.classes.entry(disassembler::mnemonic(op_code)).or_insert(0) += 1;
+
+        let current = self.call_stack.last().copied();
+        self.routines.entry(current).or_default().own += 1;
+        self.routines.entry(None).or_default().inclusive += 1;
+        for (depth, &routine) in self.call_stack.iter().enumerate() {
+            //  A recursive routine is only charged once per instruction
+            if !self.call_stack[..depth].contains(&routine) {
+                self.routines.entry(Some(routine)).or_default().inclusive += 1;
+            }
+        }
+
+        if op_code & 0xF0FF == 0xF00A && waiting_for_key {
+            self.key_wait_instructions += 1;
+            self.waited_this_frame = true;
+        }
+        if op_code & 0xF000 == 0xD000 {
+            self.draws_this_frame += 1;
+        }
+
+        match op_code {
+            _ if !executed => {}
+            0x00EE => {
+                self.call_stack.pop();
+            }
+            _ if op_code & 0xF000 == 0x2000 => {
+                let target = op_code & 0x0FFF;
+                self.routines.entry(Some(target)).or_default().calls += 1;
+                self.call_stack.push(target);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        *self.draws_per_frame.entry(self.draws_this_frame).or_insert(0) += 1;
+        self.draws_this_frame = 0;
+        if self.waited_this_frame {
+            self.key_wait_frames += 1;
+            self.waited_this_frame = false;
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn count_at(&self, address: u16) -> u64 {
+        self.address_counts[address as usize & 0x0FFF]
+    }
+
+    pub fn class_count(&self, mnemonic: &str) -> u64 {
+        self.classes.get(mnemonic).copied().unwrap_or(0)
+    }
+
+    pub fn routine(&self, address: Option<u16>) -> RoutineStats {
+        self.routines.get(&address).copied().unwrap_or_default()
+    }
+
+    //  Instructions and frames spent with FX0A waiting for a key
+    pub fn key_wait(&self) -> (u64, u64) {
+        (self.key_wait_instructions, self.key_wait_frames)
+    }
+
+    //  Number of frames for each count of DXYN executed in a frame
+    pub fn draws_per_frame(&self) -> &BTreeMap<u64, u64> {
+        &self.draws_per_frame
+    }
+
+    //  The most executed addresses, hottest first
+    pub fn hot_spots(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut addresses: Vec<(u16, u64)> = self.address_counts.iter().enumerate()
+            .filter(|(_, &executions)| executions > 0)
+            .map(|(address, &executions)| (address as u16, executions))
+            .collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(count);
+        addresses
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        match self.instructions {
+            0 => 0.0,
+            total => 100.0 * count as f64 / total as f64,
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Profile of {} instructions over {} frames", self.instructions, self.frames)?;
+
+        writeln!(f, "\nHottest addresses:")?;
+        for (address, executions) in self.hot_spots(REPORT_ROWS) {
+            let op_code = self.opcodes[address as usize];
+            writeln!(f, "  {:03X}  {:04X}  {:<18} {:>10} {:>6.2}%", address, op_code,
+                     disassembler::disassemble(op_code), executions, self.percent(executions))?;
+        }
+
+        writeln!(f, "\nOpcode classes:")?;
+        let mut classes: Vec<(&str, u64)> = self.classes.iter().map(|(&class, &count)| (class, count)).collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (class, count) in classes {
+            writeln!(f, "  {:<5} {:>10} {:>6.2}%", class, count, self.percent(count))?;
+        }
+
+        writeln!(f, "\nRoutines (instructions including callees / own):")?;
+        let mut routines: Vec<(&Option<u16>, &RoutineStats)> = self.routines.iter().collect();
+        routines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        for (address, stats) in routines.into_iter().take(REPORT_ROWS) {
+            let name = match address {
+                Some(address) => format!("{:03X}", address),
+                None => "top level".to_string(),
+            };
+            writeln!(f, "  {:<9} {:>8} calls {:>10} {:>6.2}% {:>10} {:>6.2}%", name, stats.calls,
+                     stats.inclusive, self.percent(stats.inclusive), stats.own, self.percent(stats.own))?;
+        }
+
+        writeln!(f, "\nWaiting for a key (FX0A): {} instructions, {} of {} frames",
+                 self.key_wait_instructions, self.key_wait_frames, self.frames)?;
+
+        let draws: u64 = self.draws_per_frame.iter().map(|(draws, frames)| draws * frames).sum();
+        let average = match self.frames {
+            0 => 0.0,
+            frames => draws as f64 / frames as f64,
+        };
+        let most = self.draws_per_frame.keys().next_back().copied().unwrap_or(0);
+        writeln!(f, "Draws per frame: {:.2} on average, at most {}", average, most)?;
+        for (draws, frames) in &self.draws_per_frame {
+            writeln!(f, "  {:>3} draws: {} frames", draws, frames)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::profiler::Profiler;
+
+#[cfg(test)]
+fn profile(program: Vec<u8>, cycles_per_frame: usize) -> Profiler {
+    let mut cpu = CPU::new_with_memory(program);
+    cpu.set_cycles_per_frame(cycles_per_frame);
+    cpu.set_profiler(Profiler::new());
+    cpu.run_headless().unwrap();
+    cpu.take_profiler().unwrap()
+}
+
+#[test]
+fn counts_addresses_and_classes(){
+    let profiler = profile(vec![
+        0x60, 0x03,     //  Set R0 to 3
+        0x70, 0xFF,     //  Decrement R0
+        0x30, 0x00,     //  Skip when R0 is 0
+        0x12, 0x02,     //  Loop
+    ], 1000);
+    assert_eq!(profiler.count_at(0x202), 3);
+    assert_eq!(profiler.count_at(0x206), 2);
+    assert_eq!(profiler.class_count("ADD"), 3);
+    assert_eq!(profiler.class_count("JP"), 3);
+    assert_eq!(profiler.hot_spots(2), vec![(0x202, 3), (0x204, 3)]);
+    //  The jump to 0x200, 1 + 3 * 2 + 2 loop instructions and the final 0000
+    assert_eq!(profiler.instructions(), 11);
+}
+
+#[test]
+fn groups_instructions_by_call_target(){
+    let profiler = profile(vec![
+        0x22, 0x08,     //  Call 0x208
+        0x22, 0x08,     //  Call 0x208
+        0x00, 0x00,     //  Halt
+        0x00, 0x00,
+        0x22, 0x0C,     //  0x208: call 0x20C
+        0x00, 0xEE,     //  Return
+        0x61, 0x01,     //  0x20C: set R1 to 1
+        0x00, 0xEE,     //  Return
+    ], 1000);
+    let outer = profiler.routine(Some(0x208));
+    assert_eq!((outer.calls, outer.own, outer.inclusive), (2, 4, 8));
+    let inner = profiler.routine(Some(0x20C));
+    assert_eq!((inner.calls, inner.own, inner.inclusive), (2, 4, 4));
+    let top = profiler.routine(None);
+    assert_eq!((top.own, top.inclusive), (4, 12));
+}
+
+#[test]
+fn calls_that_overflow_the_stack_are_not_counted(){
+    //  Calls itself until the sixteen entry stack is full
+    let mut cpu = CPU::new_with_memory(vec![0x22, 0x00]);
+    cpu.set_profiler(Profiler::new());
+    assert!(cpu.run_headless().is_err());
+    let profiler = cpu.take_profiler().unwrap();
+    assert_eq!(profiler.count_at(0x200), 17);
+    assert_eq!(profiler.routine(Some(0x200)).calls, 16);
+}
+
+#[test]
+fn counts_key_waits_and_draws_per_frame(){
+    let mut cpu = CPU::new_with_memory(vec![
+        0xD0, 0x01,     //  Draw
+        0xD0, 0x01,     //  Draw
+        0xF0, 0x0A,     //  Wait for a key
+    ]);
+    cpu.set_cycles_per_frame(3);
+    cpu.set_profiler(Profiler::new());
+    for _ in 0..9 {
+        cpu.step();
+    }
+    let profiler = cpu.take_profiler().unwrap();
+    //  Frames: jump and two draws, then six steps waiting for a key
+    assert_eq!(profiler.frames(), 3);
+    assert_eq!(profiler.key_wait(), (6, 2));
+    assert_eq!(profiler.draws_per_frame().get(&2), Some(&1));
+    assert_eq!(profiler.draws_per_frame().get(&0), Some(&2));
+    assert!(profiler.to_string().contains("Draws per frame: 0.67 on average, at most 2"));
+}
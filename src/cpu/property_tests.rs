@@ -0,0 +1,195 @@
+#[cfg(test)]
+use proptest::prelude::*;
+#[cfg(test)]
+use proptest::collection::vec;
+#[cfg(test)]
+use proptest::test_runner::TestCaseError;
+#[cfg(test)]
+use crate::cpu::cpu::{CPUBuilder, CPU};
+#[cfg(test)]
+use crate::cpu::instruction::{decode, Instruction};
+#[cfg(test)]
+use crate::cpu::quirks::Quirks;
+
+#[cfg(test)]
+const FRAMES: usize = 40;
+#[cfg(test)]
+const CYCLES_PER_FRAME: usize = 8;
+
+#[cfg(test)]
+fn quirks() -> impl Strategy<Value = Quirks> {
+    any::<[bool; 9]>().prop_map(|[shift, memory_increment_by_x, memory_leave_i_unchanged, wrap, jump, vblank, logic,
+                                  pointer_overflow, wide_addresses]| {
+        let address_mask = match wide_addresses {
+            true => 0xFFFF,
+            false => 0x0FFF,
+        };
+        Quirks {
+            shift, memory_increment_by_x, memory_leave_i_unchanged, wrap, jump, vblank, logic, pointer_overflow,
+            address_mask,
+        }
+    })
+}
+
+//  Random bytes fail early on unknown opcodes, so programs made only of
+//  valid ones get further
+#[cfg(test)]
+fn program() -> impl Strategy<Value = Vec<u8>> {
+    //  Unknown opcodes become 6XKK loads rather than being filtered out
+    let valid_opcode = any::<u16>().prop_map(|op_code| match decode(op_code) {
+        Ok(_) => op_code,
+        Err(_) => 0x6000 | op_code & 0x0FFF,
+    });
+    prop_oneof![
+        vec(any::<u8>(), 0..256),
+        vec(valid_opcode, 0..128).prop_map(|op_codes| op_codes.iter().flat_map(|op| op.to_be_bytes()).collect()),
+    ]
+}
+
+//  A key to press or release before each frame
+#[cfg(test)]
+fn key_events() -> impl Strategy<Value = Vec<(u8, bool)>> {
+    vec((0u8..16, any::<bool>()), FRAMES)
+}
+
+#[cfg(test)]
+fn cpu(rom: &[u8], quirks: Quirks, seed: u64) -> CPUBuilder {
+    CPU::builder()
+        .rom(rom)
+        .quirks(quirks)
+        .cycles_per_frame(CYCLES_PER_FRAME)
+        .random_seed(seed)
+}
+
+//  Where I should be after executing `op_code` with the pointer register
+//  at `i` and the registers `registers`, for the instructions that move it
+#[cfg(test)]
+fn expected_pointer(op_code: u16, i: u16, registers: &[u8; 16], quirks: Quirks) -> Option<u16> {
+    let x = op_code >> 8 & 0xF;
+    let increment = match op_code & 0xF0FF {
+        0xF01E => registers[x as usize] as u16,
+        0xF055 | 0xF065 if quirks.memory_leave_i_unchanged => 0,
+        0xF055 | 0xF065 if quirks.memory_increment_by_x => x,
+        0xF055 | 0xF065 => x + 1,
+        _ => return None,
+    };
+    Some(i.wrapping_add(increment) & quirks.address_mask)
+}
+
+//  Steps a random program, checking the invariants that hold whatever it does
+#[cfg(test)]
+fn run_checked(mut cpu: CPU, keys: &[(u8, bool)], quirks: Quirks) -> Result<(), TestCaseError> {
+    for &(key, pressed) in keys {
+        cpu.set_key(key, pressed);
+        for _ in 0..CYCLES_PER_FRAME {
+            let (pc, i, registers) = (cpu.program_counter(), cpu.pointer_register(), *cpu.registers());
+            let op_code = (cpu.memory()[pc as usize] as u16) << 8 | cpu.memory()[pc as usize + 1] as u16;
+            cpu.step();
+            prop_assert!(cpu.stack_pointer() <= 16);
+            if cpu.error().is_some() {
+                prop_assert!(cpu.is_halted());
+                return Ok(());
+            }
+            prop_assert!((cpu.program_counter() as usize) + 1 < cpu.memory().len());
+            prop_assert!(cpu.pointer_register() <= quirks.address_mask);
+            if let Some(expected) = expected_pointer(op_code, i, &registers, quirks) {
+                prop_assert_eq!(cpu.pointer_register(), expected, "after {:04X}", op_code);
+            }
+            if op_code & 0xF0FF == 0xF01E && quirks.pointer_overflow {
+                let overflow = i as usize + registers[(op_code >> 8 & 0xF) as usize] as usize > quirks.address_mask as usize;
+                prop_assert_eq!(cpu.registers()[0xF], overflow as u8, "after {:04X}", op_code);
+            }
+            if cpu.is_halted() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+//  Runs a random program a frame at a time, the way the frontend does
+#[cfg(test)]
+fn run_frames(mut cpu: CPU, keys: &[(u8, bool)]) -> CPU {
+    for &(key, pressed) in keys {
+        cpu.set_key(key, pressed);
+        cpu.run_frame();
+    }
+    cpu
+}
+
+//  The result and VF of an arithmetic instruction, as the CHIP-8 documents have it
+#[cfg(test)]
+fn reference_alu(instruction: Instruction, vx: u8, vy: u8, quirks: Quirks) -> (u8, u8) {
+    let shifted = match quirks.shift {
+        true => vx,
+        false => vy,
+    };
+    match instruction {
+        Instruction::Add { .. } => (vx.wrapping_add(vy), (vx as u16 + vy as u16 > 0xFF) as u8),
+        Instruction::Sub { .. } => (vx.wrapping_sub(vy), (vx >= vy) as u8),
+        Instruction::SubReversed { .. } => (vy.wrapping_sub(vx), (vy >= vx) as u8),
+        Instruction::ShiftRight { .. } => (shifted >> 1, shifted & 1),
+        Instruction::ShiftLeft { .. } => (shifted << 1, shifted >> 7),
+        _ => unreachable!("no reference for {}", instruction),
+    }
+}
+
+#[cfg(test)]
+fn check_alu(instruction: Instruction, x: u8, y: u8, registers: [u8; 16], quirks: Quirks) -> Result<(), TestCaseError> {
+    let mut cpu = CPU::builder().registers(registers).quirks(quirks).rom(&instruction.encode().to_be_bytes()).build().unwrap();
+    cpu.step();
+    let (result, flag) = reference_alu(instruction, registers[x as usize], registers[y as usize], quirks);
+    //  The flag is written last, so it wins when X is VF
+    match x {
+        0xF => prop_assert_eq!(cpu.registers()[0xF], flag, "{}", instruction),
+        _ => {
+            prop_assert_eq!(cpu.registers()[x as usize], result, "{}", instruction);
+            prop_assert_eq!(cpu.registers()[0xF], flag, "{}", instruction);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn random_programs_never_break_the_invariants(rom in program(), keys in key_events(),
+                                                  quirks in quirks(), seed in any::<u64>()) {
+        run_checked(cpu(&rom, quirks, seed).build().unwrap(), &keys, quirks)?;
+    }
+
+    #[test]
+    fn decode_cache_and_basic_blocks_do_not_change_random_programs(
+            rom in program(), keys in key_events(), quirks in quirks(), seed in any::<u64>()) {
+        let plain = run_frames(cpu(&rom, quirks, seed).build().unwrap(), &keys);
+        for (decode_cache, basic_blocks) in [(true, false), (false, true)] {
+            let other = run_frames(cpu(&rom, quirks, seed).decode_cache(decode_cache).basic_blocks(basic_blocks)
+                .build().unwrap(), &keys);
+            prop_assert_eq!(other.error(), plain.error());
+            prop_assert_eq!(other.registers(), plain.registers());
+            prop_assert_eq!(other.program_counter(), plain.program_counter());
+            prop_assert_eq!(other.pointer_register(), plain.pointer_register());
+            prop_assert_eq!(other.stack(), plain.stack());
+            prop_assert_eq!(other.delay_timer(), plain.delay_timer());
+            prop_assert_eq!(other.memory(), plain.memory());
+            prop_assert_eq!(other.framebuffer(), plain.framebuffer());
+        }
+    }
+
+    #[test]
+    fn add_matches_the_reference(x in 0u8..16, y in 0u8..16, registers in any::<[u8; 16]>(), quirks in quirks()) {
+        check_alu(Instruction::Add { x, y }, x, y, registers, quirks)?;
+    }
+
+    #[test]
+    fn subtractions_match_the_reference(x in 0u8..16, y in 0u8..16, registers in any::<[u8; 16]>(), quirks in quirks()) {
+        check_alu(Instruction::Sub { x, y }, x, y, registers, quirks)?;
+        check_alu(Instruction::SubReversed { x, y }, x, y, registers, quirks)?;
+    }
+
+    #[test]
+    fn shifts_match_the_reference(x in 0u8..16, y in 0u8..16, registers in any::<[u8; 16]>(), quirks in quirks()) {
+        check_alu(Instruction::ShiftRight { x, y }, x, y, registers, quirks)?;
+        check_alu(Instruction::ShiftLeft { x, y }, x, y, registers, quirks)?;
+    }
+}
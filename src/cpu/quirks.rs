@@ -0,0 +1,168 @@
+use std::str::FromStr;
+use crate::cpu::font::FontSet;
+
+//  Behaviours that differ between CHIP-8 interpreters. The names follow the
+//  chip-8-database (https://github.com/chip-8/chip-8-database) quirk names.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    //  "shift": 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub shift: bool,
+    //  "memoryIncrementByX": FX55/FX65 leave I incremented by X instead of X + 1
+    pub memory_increment_by_x: bool,
+    //  "memoryLeaveIUnchanged": FX55/FX65 do not touch I at all
+    pub memory_leave_i_unchanged: bool,
+    //  "wrap": sprites wrap around the edges of the screen instead of being clipped
+    pub wrap: bool,
+    //  "jump": BNNN behaves as BXNN, jumping to XNN + VX
+    pub jump: bool,
+    //  "vblank": drawing waits for the vertical blank, so at most one sprite is drawn per frame
+    pub vblank: bool,
+    //  "logic": 8XY1, 8XY2 and 8XY3 reset VF
+    pub logic: bool,
+    //  "pointerOverflow", not in the database: FX1E sets VF when I leaves
+    //  the address space and clears it otherwise, as on the Amiga interpreter
+    pub pointer_overflow: bool,
+    //  The address space I wraps around in, 12 bits on all but XO-CHIP
+    pub address_mask: u16,
+}
+
+impl Default for Quirks {
+    //  What this interpreter has always done
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+            pointer_overflow: false,
+            address_mask: 0x0FFF,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            "pointerOverflow" => &mut self.pointer_overflow,
+            _ => return Err(format!("unknown quirk '{}'", name)),
+        };
+        *quirk = enabled;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    OriginalChip8,
+    HybridVip,
+    ModernChip8,
+    Chip48,
+    SuperChip1,
+    SuperChip,
+    XoChip,
+}
+
+const PLATFORM_IDS: [(&str, Platform); 7] = [
+    ("originalChip8", Platform::OriginalChip8),
+    ("hybridVIP", Platform::HybridVip),
+    ("modernChip8", Platform::ModernChip8),
+    ("chip48", Platform::Chip48),
+    ("superchip1", Platform::SuperChip1),
+    ("superchip", Platform::SuperChip),
+    ("xochip", Platform::XoChip),
+];
+
+impl Platform {
+    //  The identifier used by the chip-8-database
+    pub fn id(&self) -> &'static str {
+        PLATFORM_IDS.iter()
+            .find(|(_, platform)| platform == self)
+            .map(|&(id, _)| id)
+            .unwrap()
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+            pointer_overflow: false,
+            address_mask: 0x0FFF,
+        };
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => {
+                quirks.vblank = true;
+                quirks.logic = true;
+            }
+            Platform::ModernChip8 => (),
+            Platform::Chip48 => {
+                quirks.shift = true;
+                quirks.memory_increment_by_x = true;
+                quirks.jump = true;
+            }
+            Platform::SuperChip1 | Platform::SuperChip => {
+                quirks.shift = true;
+                quirks.memory_leave_i_unchanged = true;
+                quirks.jump = true;
+            }
+            Platform::XoChip => {
+                quirks.wrap = true;
+                quirks.address_mask = 0xFFFF;
+            }
+        }
+        quirks
+    }
+
+    //  The font the platform's interpreter came with
+    pub fn font_set(&self) -> FontSet {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => FontSet::CosmacVip,
+            Platform::SuperChip1 | Platform::SuperChip => FontSet::SuperChip,
+            Platform::ModernChip8 | Platform::Chip48 | Platform::XoChip => FontSet::Standard,
+        }
+    }
+
+    //  Return addresses the platform's interpreter has room for
+    pub fn stack_depth(&self) -> usize {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => 12,
+            Platform::Chip48 | Platform::SuperChip1 | Platform::SuperChip => 16,
+            Platform::ModernChip8 | Platform::XoChip => 32,
+        }
+    }
+
+    //  Instructions per 60 Hz frame usually expected by programs for the platform
+    pub fn tickrate(&self) -> usize {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => 15,
+            Platform::ModernChip8 | Platform::Chip48 => 12,
+            Platform::SuperChip1 | Platform::SuperChip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PLATFORM_IDS.iter()
+            .find(|(id, _)| *id == s)
+            .map(|&(_, platform)| platform)
+            .ok_or_else(|| format!("unknown platform '{}'", s))
+    }
+}
@@ -0,0 +1,126 @@
+//  Return addresses most interpreters have room for
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+//  The 4 KB COSMAC VIP's stack grows down from 0xECF
+pub const VIP_STACK_TOP: u16 = 0xED0;
+//  ... and may not reach below 0xEA0, which leaves room for 24 addresses
+pub const VIP_STACK_BOTTOM: u16 = 0xEA0;
+pub const VIP_STACK_DEPTH: usize = (VIP_STACK_TOP - VIP_STACK_BOTTOM) as usize / 2;
+//  Far more than any interpreter ever had room for
+pub const MAX_STACK_DEPTH: usize = 1024;
+
+//  Where the return addresses of 2NNN are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StackStorage {
+    //  Outside addressable memory, where programs cannot see them
+    #[default]
+    Internal,
+    //  In memory below `top`, two bytes per call, most significant first, as
+    //  on the COSMAC VIP. Programs that read or overwrite them get what
+    //  they would on the VIP.
+    Memory { top: u16 },
+}
+
+//  The call stack: `depth` return addresses at most
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stack {
+    depth: usize,
+    storage: StackStorage,
+    //  The addresses themselves when they are kept internally
+    internal: Vec<u16>,
+    len: usize,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Stack::new(DEFAULT_STACK_DEPTH, StackStorage::Internal).unwrap()
+    }
+}
+
+impl Stack {
+    pub fn new(depth: usize, storage: StackStorage) -> Result<Stack, String> {
+        if depth > MAX_STACK_DEPTH {
+            return Err(format!("a stack of {} return addresses is deeper than {}", depth, MAX_STACK_DEPTH));
+        }
+        if let StackStorage::Memory { top } = storage {
+            if top > 0x1000 {
+                return Err(format!("the stack top {:03X} is past the end of memory", top));
+            }
+            //  The VIP's stack has its own region, anywhere else it may
+            //  take all of memory below its top
+            let bottom = if top == VIP_STACK_TOP { VIP_STACK_BOTTOM } else { 0 };
+            if 2 * depth > (top - bottom) as usize {
+                return Err(format!("{} return addresses do not fit between {:03X} and {:03X}", depth, bottom, top));
+            }
+        }
+        Ok(Stack { depth, storage, internal: Vec::new(), len: 0 })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn storage(&self) -> StackStorage {
+        self.storage
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    //  Where the `index`th address from the bottom is kept in memory
+    fn slot(top: u16, index: usize) -> usize {
+        top as usize - 2 * (index + 1)
+    }
+
+    //  Pushes `address`, returning where in memory it went if it went
+    //  there, or None when the stack is full
+    pub fn push(&mut self, address: u16, memory: &mut [u8]) -> Option<Option<usize>> {
+        if self.len >= self.depth {
+            return None;
+        }
+        let written = match self.storage {
+            StackStorage::Internal => {
+                self.internal.push(address);
+                None
+            }
+            StackStorage::Memory { top } => {
+                let slot = Stack::slot(top, self.len);
+                memory[slot..slot + 2].copy_from_slice(&address.to_be_bytes());
+                Some(slot)
+            }
+        };
+        self.len += 1;
+        Some(written)
+    }
+
+    //  The most recent return address, read back from memory when it is
+    //  kept there
+    pub fn pop(&mut self, memory: &[u8]) -> Option<u16> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        match self.storage {
+            StackStorage::Internal => self.internal.pop(),
+            StackStorage::Memory { top } => Some(Stack::read(memory, Stack::slot(top, self.len))),
+        }
+    }
+
+    //  The return addresses, oldest first
+    pub fn addresses(&self, memory: &[u8]) -> Vec<u16> {
+        match self.storage {
+            StackStorage::Internal => self.internal.clone(),
+            StackStorage::Memory { top } => (0..self.len)
+                .map(|index| Stack::read(memory, Stack::slot(top, index)))
+                .collect(),
+        }
+    }
+
+    fn read(memory: &[u8], slot: usize) -> u16 {
+        u16::from_be_bytes([memory[slot], memory[slot + 1]])
+    }
+}
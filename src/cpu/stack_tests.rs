@@ -0,0 +1,95 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::error::ExecutionError;
+#[cfg(test)]
+use crate::cpu::quirks::Platform;
+#[cfg(test)]
+use crate::cpu::stack::{Stack, StackStorage, MAX_STACK_DEPTH, VIP_STACK_DEPTH, VIP_STACK_TOP};
+
+#[cfg(test)]
+const VIP_STACK: StackStorage = StackStorage::Memory { top: VIP_STACK_TOP };
+
+#[test]
+fn platforms_have_their_own_stack_depth(){
+    assert_eq!(Platform::OriginalChip8.stack_depth(), 12);
+    assert_eq!(Platform::SuperChip.stack_depth(), 16);
+    assert!(Platform::XoChip.stack_depth() > 16);
+}
+
+#[test]
+fn the_stack_overflows_at_its_depth(){
+    let mut cpu = CPU::builder().stack_layout(12, StackStorage::Internal).rom(&[0x22, 0x00]).build().unwrap();
+    assert_eq!(cpu.run_headless(), Err(ExecutionError::StackOverflow { address: 0x200 }));
+    assert_eq!(cpu.stack_pointer(), 12);
+}
+
+#[test]
+fn deep_stacks_hold_more_calls(){
+    let cpu = CPU::builder().stack_layout(32, StackStorage::Internal).stack(&[0x200; 32]).build().unwrap();
+    assert_eq!(cpu.stack().len(), 32);
+    assert!(CPU::builder().stack_layout(32, StackStorage::Internal).stack(&[0x200; 33]).build().is_err());
+}
+
+#[test]
+fn vip_stacks_grow_down_from_0xecf(){
+    let mut cpu = CPU::builder().stack_layout(12, VIP_STACK)
+        .rom(&[0x22, 0x04, 0x00, 0x00, 0x23, 0x00])
+        .build().unwrap();
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.memory()[0xECC..0xED0], [0x02, 0x06, 0x02, 0x02]);
+    assert_eq!(cpu.stack(), &[0x202, 0x206]);
+}
+
+#[test]
+fn returns_read_the_address_back_from_memory(){
+    //  The subroutine overwrites its own return address with 0x208
+    let mut cpu = CPU::builder().stack_layout(12, VIP_STACK).pointer_register(0xECE).rom(&[
+        0x22, 0x06,     //  Call 0x206
+        0x00, 0x00,     //  Halt, which the subroutine skips
+        0x00, 0x00,
+        0x60, 0x02,     //  Set R0 to 2
+        0x61, 0x08,     //  Set R1 to 8
+        0xF1, 0x55,     //  Store R0 and R1 over the return address
+        0x00, 0xEE,     //  Return
+    ]).build().unwrap();
+    for _ in 0..5 {
+        cpu.step();
+    }
+    assert_eq!(cpu.program_counter(), 0x208);
+    assert!(cpu.stack().is_empty());
+}
+
+#[test]
+fn stacks_in_memory_must_fit_below_their_top(){
+    assert!(Stack::new(12, StackStorage::Memory { top: 0x018 }).is_ok());
+    assert!(Stack::new(13, StackStorage::Memory { top: 0x018 }).is_err());
+    assert!(Stack::new(1, StackStorage::Memory { top: 0x1002 }).is_err());
+}
+
+#[test]
+fn vip_stacks_stay_above_0xea0(){
+    assert_eq!(VIP_STACK_DEPTH, 24);
+    assert!(Stack::new(24, VIP_STACK).is_ok());
+    assert!(Stack::new(32, VIP_STACK).is_err());
+}
+
+#[test]
+fn stacks_have_a_maximum_depth(){
+    assert!(Stack::new(MAX_STACK_DEPTH, StackStorage::Internal).is_ok());
+    assert!(Stack::new(usize::MAX, StackStorage::Internal).is_err());
+    assert!(CPU::builder().stack_layout(usize::MAX, StackStorage::Internal).build().is_err());
+}
+
+#[test]
+fn loading_a_rom_keeps_the_stack_layout(){
+    let mut cpu = CPU::builder().stack_layout(12, VIP_STACK).stack(&[0x300]).build().unwrap();
+    cpu.load_rom(&[0x22, 0x00]).unwrap();
+    assert!(cpu.stack().is_empty());
+    assert_eq!(cpu.stack_depth(), 12);
+    //  The jump to 0x200, then the call
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.memory()[0xECE..0xED0], [0x02, 0x02]);
+}
@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingMode {
+    //  Every instruction takes one cycle; a frame lasts `cycles_per_frame` instructions
+    Instructions,
+    //  Every instruction takes as many 1802 machine cycles as the COSMAC VIP
+    //  interpreter spends on it, and DXYN waits for the vertical blank
+    CosmacVip,
+}
+
+impl FromStr for TimingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "instructions" => Ok(TimingMode::Instructions),
+            "vip" => Ok(TimingMode::CosmacVip),
+            _ => Err(format!("unknown timing mode '{}' (expected instructions or vip)", s)),
+        }
+    }
+}
+
+//  The VIP's 1802 runs at 1.7609 MHz with 8 clocks per machine cycle, which
+//  gives 3668 machine cycles per 60 Hz frame. The CDP1861 display steals
+//  1024 of them for DMA (8 bytes on each of 128 lines) and the interrupt
+//  routine that services the timers takes a few more.
+pub const VIP_CYCLES_PER_FRAME: usize = 3668;
+const VIP_DISPLAY_DMA_CYCLES: usize = 1024;
+const VIP_INTERRUPT_CYCLES: usize = 46;
+pub const VIP_CYCLES_AVAILABLE_PER_FRAME: usize =
+    VIP_CYCLES_PER_FRAME - VIP_DISPLAY_DMA_CYCLES - VIP_INTERRUPT_CYCLES;
+
+//  Fetching, decoding and dispatching any instruction
+const VIP_FETCH_CYCLES: usize = 40;
+
+//  Machine cycles the VIP interpreter spends executing `op_code`, given the
+//  register values it is executed with. The costs follow the routines of the
+//  original interpreter; the variable ones are modelled on the loops they run.
+pub fn vip_machine_cycles(op_code: u16, registers: &[u8; 16]) -> usize {
+    let x = ((op_code & 0x0F00) >> 8) as usize;
+    let n = (op_code & 0x000F) as usize;
+
+    let execution = match op_code >> 12 {
+        0x0 => match op_code {
+            0x00E0 => 24 + 3078,            //  Clears 256 bytes of display memory
+            0x00EE => 10,
+            _ => 0,
+        },
+        0x1 => 12,
+        0x2 => 26,
+        0x3 | 0x4 => 10,
+        0x5 | 0x9 => 14,
+        0x6 => 6,
+        0x7 => 10,
+        0x8 => 44,
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        0xD => {
+            //  Each row is shifted into place one bit at a time, then written
+            //  over two display bytes
+            let shift = registers[x] as usize % 8;
+            26 + n * (46 + 8 * shift)
+        }
+        0xE => 14,
+        0xF => match op_code & 0x00FF {
+            //  FX0A is charged again on every cycle it keeps waiting
+            0x07 | 0x0A | 0x15 | 0x18 => 10,
+            0x1E => 16,
+            0x29 => 16,
+            0x33 => {
+                //  Repeated subtraction: one pass per unit of every digit
+                let value = registers[x] as usize;
+                80 + 16 * (value / 100 + (value / 10) % 10 + value % 10)
+            }
+            0x55 | 0x65 => 14 + 14 * (x + 1),
+            _ => 0,
+        },
+        _ => 0,
+    };
+
+    VIP_FETCH_CYCLES + execution
+}
@@ -0,0 +1,295 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    //  Aligned columns, meant to be read
+    Text,
+    //  One header line, then comma separated values
+    Csv,
+    //  One JSON object per line
+    JsonLines,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "csv" => Ok(TraceFormat::Csv),
+            "jsonl" => Ok(TraceFormat::JsonLines),
+            _ => Err(format!("unknown trace format '{}' (expected text, csv or jsonl)", s)),
+        }
+    }
+}
+
+impl TraceFormat {
+    //  Guesses the format from the extension of a trace file
+    pub fn for_path(path: &Path) -> Option<TraceFormat> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(TraceFormat::Csv),
+            "jsonl" | "json" => Some(TraceFormat::JsonLines),
+            "txt" | "log" => Some(TraceFormat::Text),
+            _ => None,
+        }
+    }
+}
+
+//  Positions of the columns in the CSV header
+const CYCLE: usize = 0;
+const PC: usize = 1;
+const OPCODE: usize = 2;
+const DISASSEMBLY: usize = 3;
+const V0: usize = 4;
+const I: usize = 20;
+const DT: usize = 21;
+const ST: usize = 22;
+const COLUMN_COUNT: usize = 23;
+
+//  The columns a record holds, as bits numbered by their position in the
+//  CSV header. Traces of other emulators may leave some columns out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Columns(u32);
+
+impl Columns {
+    pub fn all() -> Columns {
+        Columns((1 << COLUMN_COUNT) - 1)
+    }
+
+    fn with(self, column: usize) -> Columns {
+        Columns(self.0 | 1 << column)
+    }
+
+    fn contains(self, column: usize) -> bool {
+        self.0 & 1 << column != 0
+    }
+}
+
+//  One executed instruction; the machine state is the one left after executing it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub disassembly: String,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    //  What the trace provided; only those columns are compared
+    #[serde(skip, default = "Columns::all")]
+    pub columns: Columns,
+}
+
+impl TraceRecord {
+    pub fn to_text(&self) -> String {
+        let mut line = format!("{:>10}  {:03X}  {:04X}  {:<18}",
+                               self.cycle, self.pc, self.opcode, self.disassembly);
+        for (index, register) in self.registers.iter().enumerate() {
+            write!(line, " V{:X}={:02X}", index, register).unwrap();
+        }
+        write!(line, "  I={:03X} DT={:02X} ST={:02X}", self.i, self.delay_timer, self.sound_timer).unwrap();
+        line
+    }
+
+    pub fn csv_header() -> String {
+        column_names().join(",")
+    }
+
+    pub fn to_csv(&self) -> String {
+        //  The disassembly contains commas, so it is always quoted
+        let mut line = format!("{},0x{:03X},0x{:04X},\"{}\"",
+                               self.cycle, self.pc, self.opcode, self.disassembly);
+        for register in self.registers.iter() {
+            write!(line, ",{}", register).unwrap();
+        }
+        write!(line, ",0x{:03X},{},{}", self.i, self.delay_timer, self.sound_timer).unwrap();
+        line
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    //  Reads a row in the layout of `csv_header`, without or with the
+    //  disassembly, which may be left unquoted. Numbers may be written in
+    //  decimal or with a 0x prefix.
+    pub fn from_csv(line: &str) -> Result<TraceRecord, String> {
+        let layout: Vec<Option<usize>> = match split_csv(line).len() {
+            count if count == COLUMN_COUNT - 1 => (0..COLUMN_COUNT).filter(|&c| c != DISASSEMBLY).map(Some).collect(),
+            _ => (0..COLUMN_COUNT).map(Some).collect(),
+        };
+        TraceRecord::from_csv_columns(line, &layout)
+    }
+
+    //  Reads a row whose fields are the columns of `layout` in order; None
+    //  marks a column this emulator does not know, which is skipped
+    fn from_csv_columns(line: &str, layout: &[Option<usize>]) -> Result<TraceRecord, String> {
+        let mut fields = split_csv(line);
+        //  An unquoted disassembly is split at its commas
+        if let Some(position) = layout.iter().position(|&column| column == Some(DISASSEMBLY)) {
+            if fields.len() > layout.len() {
+                let extra = fields.len() - layout.len();
+                let joined = fields.drain(position..=position + extra).collect::<Vec<_>>().join(",");
+                fields.insert(position, joined);
+            }
+        }
+        if fields.len() != layout.len() {
+            return Err(format!("expected {} columns in '{}'", layout.len(), line));
+        }
+        let mut record = TraceRecord::default();
+        for (field, column) in fields.iter().zip(layout) {
+            if let Some(column) = *column {
+                record.set(column, field)?;
+            }
+        }
+        Ok(record)
+    }
+
+    fn set(&mut self, column: usize, field: &str) -> Result<(), String> {
+        match column {
+            CYCLE => self.cycle = parse_number(field)?,
+            PC => self.pc = parse_number(field)? as u16,
+            OPCODE => self.opcode = parse_number(field)? as u16,
+            DISASSEMBLY => self.disassembly = field.trim().to_string(),
+            I => self.i = parse_number(field)? as u16,
+            DT => self.delay_timer = parse_number(field)? as u8,
+            ST => self.sound_timer = parse_number(field)? as u8,
+            _ => self.registers[column - V0] = parse_number(field)? as u8,
+        }
+        self.columns = self.columns.with(column);
+        Ok(())
+    }
+
+    pub fn from_json(line: &str) -> Result<TraceRecord, String> {
+        serde_json::from_str(line).map_err(|e| format!("invalid trace record '{}': {}", line, e))
+    }
+
+    //  The machine state that differs from `reference`, among the columns
+    //  the reference provides. Cycle counters and mnemonics vary between
+    //  emulators, so they are not compared.
+    pub fn differences(&self, reference: &TraceRecord) -> Vec<String> {
+        let mut differences = Vec::new();
+        let provided = reference.columns;
+        if provided.contains(PC) && self.pc != reference.pc {
+            differences.push(format!("PC: {:03X} != {:03X}", self.pc, reference.pc));
+        }
+        if provided.contains(OPCODE) && self.opcode != reference.opcode {
+            differences.push(format!("opcode: {:04X} != {:04X}", self.opcode, reference.opcode));
+        }
+        for (index, (ours, theirs)) in self.registers.iter().zip(&reference.registers).enumerate() {
+            if provided.contains(V0 + index) && ours != theirs {
+                differences.push(format!("V{:X}: {:02X} != {:02X}", index, ours, theirs));
+            }
+        }
+        if provided.contains(I) && self.i != reference.i {
+            differences.push(format!("I: {:03X} != {:03X}", self.i, reference.i));
+        }
+        if provided.contains(DT) && self.delay_timer != reference.delay_timer {
+            differences.push(format!("DT: {:02X} != {:02X}", self.delay_timer, reference.delay_timer));
+        }
+        if provided.contains(ST) && self.sound_timer != reference.sound_timer {
+            differences.push(format!("ST: {:02X} != {:02X}", self.sound_timer, reference.sound_timer));
+        }
+        differences
+    }
+}
+
+fn parse_number(field: &str) -> Result<u64, String> {
+    let field = field.trim();
+    let parsed = match field.strip_prefix("0x").or_else(|| field.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => field.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number '{}' in trace", field))
+}
+
+fn column_names() -> Vec<String> {
+    let mut names: Vec<String> = ["cycle", "pc", "opcode", "disassembly"].iter().map(|name| name.to_string()).collect();
+    names.extend((0..16).map(|index| format!("v{:x}", index)));
+    names.extend(["i", "dt", "st"].iter().map(|name| name.to_string()));
+    names
+}
+
+//  Splits a CSV row at the commas outside double quotes, dropping the quotes
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+//  Maps the columns of a CSV header onto ours; columns this emulator does
+//  not record are skipped, and the ones left out are not compared
+fn csv_layout(header: &str) -> Vec<Option<usize>> {
+    let names = column_names();
+    split_csv(header).iter()
+        .map(|field| names.iter().position(|name| name.eq_ignore_ascii_case(field.trim())))
+        .collect()
+}
+
+//  Reads a whole CSV or JSON Lines trace; the text format is only meant to be read.
+//  A CSV trace may start with a header naming its columns, in any order.
+pub fn read_trace(text: &str, format: TraceFormat) -> Result<Vec<TraceRecord>, String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty()).peekable();
+    match format {
+        TraceFormat::Csv => {
+            let header = lines.next_if(|line| parse_number(&split_csv(line)[0]).is_err());
+            match header {
+                Some(header) => {
+                    let layout = csv_layout(header);
+                    if !layout.iter().any(Option::is_some) {
+                        return Err(format!("no known columns in the trace header '{}'", header));
+                    }
+                    lines.filter(|&line| line != header)
+                        .map(|line| TraceRecord::from_csv_columns(line, &layout))
+                        .collect()
+                }
+                None => lines.map(TraceRecord::from_csv).collect(),
+            }
+        }
+        TraceFormat::JsonLines => lines.map(TraceRecord::from_json).collect(),
+        TraceFormat::Text => Err("text traces cannot be read back; use csv or jsonl".to_string()),
+    }
+}
+
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    header_written: bool,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, format: TraceFormat) -> Tracer {
+        Tracer { output, format, header_written: false }
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        let line = match self.format {
+            TraceFormat::Text => record.to_text(),
+            TraceFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.output, "{}", TraceRecord::csv_header())?;
+                    self.header_written = true;
+                }
+                record.to_csv()
+            }
+            TraceFormat::JsonLines => record.to_json(),
+        };
+        writeln!(self.output, "{}", line)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
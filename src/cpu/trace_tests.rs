@@ -0,0 +1,124 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::cpu::trace::{read_trace, Columns, TraceFormat, TraceRecord};
+#[cfg(test)]
+use crate::cpu::tracediff;
+
+#[cfg(test)]
+fn reference_run(program: Vec<u8>) -> Vec<TraceRecord> {
+    let mut cpu = CPU::new_with_memory(program);
+    let mut records = Vec::new();
+    while !cpu.is_halted() {
+        records.push(cpu.step_with_record());
+    }
+    records
+}
+
+#[test]
+fn csv_records_read_back(){
+    let record = TraceRecord {
+        cycle: 7,
+        pc: 0x2A4,
+        opcode: 0x8124,
+        disassembly: "ADD V1, V2".to_string(),
+        registers: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 255],
+        i: 0x3FF,
+        delay_timer: 60,
+        sound_timer: 4,
+        columns: Columns::all(),
+    };
+    let text = format!("{}\n{}\n", TraceRecord::csv_header(), record.to_csv());
+    assert_eq!(read_trace(&text, TraceFormat::Csv), Ok(vec![record.clone()]));
+    assert_eq!(read_trace(&record.to_json(), TraceFormat::JsonLines), Ok(vec![record]));
+}
+
+#[test]
+fn csv_records_accept_decimal_and_hex(){
+    let line = "3,512,0x6001,\"\",0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x10,768,0,0";
+    let record = TraceRecord::from_csv(line).unwrap();
+    assert_eq!((record.pc, record.opcode, record.registers[15], record.i), (0x200, 0x6001, 0x10, 0x300));
+    assert!(TraceRecord::from_csv("3,512,0x6001,0,0").is_err());
+}
+
+#[test]
+fn csv_records_may_leave_the_disassembly_unquoted_or_out(){
+    let quoted = TraceRecord::from_csv("3,0x204,0x8014,\"ADD V0, V1\",8,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0").unwrap();
+    let unquoted = TraceRecord::from_csv("3,0x204,0x8014,ADD V0, V1,8,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0").unwrap();
+    assert_eq!(unquoted, quoted);
+    let missing = TraceRecord::from_csv("3,0x204,0x8014,8,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0").unwrap();
+    assert_eq!((missing.pc, missing.registers[0], missing.registers[1]), (0x204, 8, 3));
+    assert!(missing.differences(&quoted).is_empty());
+}
+
+#[test]
+fn only_the_columns_a_reference_provides_are_compared(){
+    //  A trace that records neither cycles, disassembly, nor timers
+    let reference = read_trace(&[
+        "PC,Opcode,V0,V1,I",
+        "0x200,0x6005,5,0,0x000",
+        "0x202,0xF015,5,0,0x000",
+        "0x204,0x6103,5,4,0x000",
+    ].join("\n"), TraceFormat::Csv).unwrap();
+    let mut cpu = CPU::new_with_memory(vec![0x60, 0x05, 0xF0, 0x15, 0x61, 0x03]);
+    let divergence = tracediff::compare(&mut cpu, &reference).unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(divergence.differences, vec!["V1: 03 != 04".to_string()]);
+}
+
+#[test]
+fn identical_runs_do_not_diverge(){
+    let program = vec![0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0xA3, 0x00, 0xF0, 0x33];
+    let reference = reference_run(program.clone());
+    let mut cpu = CPU::new_with_memory(program);
+    assert!(tracediff::compare(&mut cpu, &reference).is_none());
+}
+
+#[test]
+fn first_divergence_is_reported(){
+    let program = vec![0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0xA3, 0x00];
+    let mut reference = reference_run(program.clone());
+    reference[2].registers[0] = 9;
+    reference[3].i = 0x400;
+    let mut cpu = CPU::new_with_memory(program);
+    let divergence = tracediff::compare(&mut cpu, &reference).unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(divergence.differences, vec!["V0: 08 != 09".to_string()]);
+}
+
+#[test]
+fn traces_from_emulators_starting_at_0x200_line_up(){
+    //  Written by hand, as an emulator without a boot jump would trace it
+    let reference = read_trace(&[
+        "cycle,pc,opcode,disassembly,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,dt,st",
+        "1,0x200,0x6005,\"LD V0, 5\",5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0",
+        "2,0x202,0x6103,\"LD V1, 3\",5,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0",
+        "3,0x204,0x8014,\"ADD V0, V1\",8,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x000,0,0",
+        "4,0x206,0xA300,\"LD I, 0x300\",8,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0x300,0,0",
+    ].join("\n"), TraceFormat::Csv).unwrap();
+    let mut cpu = CPU::new_with_memory(vec![0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0xA3, 0x00]);
+    assert!(tracediff::compare(&mut cpu, &reference).is_none());
+}
+
+#[test]
+fn sound_timers_are_compared(){
+    //  Start a tone of 5
+    let program = vec![0x60, 0x05, 0xF0, 0x18];
+    let mut reference = reference_run(program.clone());
+    let sound_timer = reference[1].sound_timer;
+    assert!(sound_timer > 0);
+    reference[1].sound_timer = 0;
+    let mut cpu = CPU::new_with_memory(program);
+    let divergence = tracediff::compare(&mut cpu, &reference).unwrap();
+    assert_eq!(divergence.differences, vec![format!("ST: {:02X} != 00", sound_timer)]);
+}
+
+#[test]
+fn halting_early_is_a_divergence(){
+    let mut reference = reference_run(vec![0x60, 0x05]);
+    reference.push(reference[reference.len() - 1].clone());
+    let mut cpu = CPU::new_with_memory(vec![0x60, 0x05]);
+    let divergence = tracediff::compare(&mut cpu, &reference).unwrap();
+    assert_eq!(divergence.step, reference.len() - 1);
+    assert!(divergence.ours.is_none());
+}
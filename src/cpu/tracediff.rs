@@ -0,0 +1,63 @@
+use std::fmt;
+use crate::cpu::cpu::CPU;
+use crate::cpu::trace::TraceRecord;
+
+//  Bytes of memory shown from I when the traces diverge
+const MEMORY_WINDOW: usize = 16;
+
+//  The first step at which the emulator and the reference trace disagree
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub step: usize,
+    pub reference: TraceRecord,
+    //  None when the program halted before the reference trace ended
+    pub ours: Option<TraceRecord>,
+    pub differences: Vec<String>,
+    //  What the emulator has in memory from I, which FX33, FX55 and DXYN use
+    pub memory: Vec<u8>,
+}
+
+//  Steps `cpu` once for every record of `reference` and stops at the first
+//  record it does not match. Running past the end of the reference is not
+//  checked, so a trace of the first N steps validates just those.
+pub fn compare(cpu: &mut CPU, reference: &[TraceRecord]) -> Option<Divergence> {
+    for (step, expected) in reference.iter().enumerate() {
+        let ours = match cpu.is_halted() {
+            true => None,
+            false => Some(cpu.step_with_record()),
+        };
+        let differences = match &ours {
+            Some(record) => record.differences(expected),
+            None => match cpu.error() {
+                Some(error) => vec![format!("the program failed: {}", error)],
+                None => vec!["the program halted".to_string()],
+            },
+        };
+        if !differences.is_empty() {
+            let i = ours.as_ref().map_or(expected.i, |record| record.i) as usize;
+            let memory = cpu.memory().iter().skip(i).take(MEMORY_WINDOW).copied().collect();
+            return Some(Divergence { step, reference: expected.clone(), ours, differences, memory });
+        }
+    }
+    None
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Traces diverge at step {} (reference cycle {})", self.step, self.reference.cycle)?;
+        writeln!(f, "  reference: {}", self.reference.to_text())?;
+        if let Some(ours) = &self.ours {
+            writeln!(f, "  emulator:  {}", ours.to_text())?;
+        }
+        writeln!(f, "Differences (emulator != reference):")?;
+        for difference in &self.differences {
+            writeln!(f, "  {}", difference)?;
+        }
+        let i = self.ours.as_ref().map_or(self.reference.i, |record| record.i);
+        write!(f, "Memory at I ({:03X}):", i)?;
+        for byte in &self.memory {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+use crate::cpu::font::FontSet;
+use crate::cpu::machine_code::MachineCodePolicy;
+use crate::cpu::quirks::Platform;
+use crate::cpu::timing::TimingMode;
+use crate::cpu::trace::TraceFormat;
+use crate::frontend::window::{Palette, WindowSettings};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Command {
+    //  Runs the ROM in a window
+    #[default]
+    Run,
+    //  Runs the ROM headless and compares it with a reference trace:
+    //  chip_8 tracediff [options] <rom> <reference trace>
+    TraceDiff,
+}
+
+//  --font takes the name of a built-in font set or the path of a font file
+#[derive(Clone, Debug, PartialEq)]
+pub enum FontChoice {
+    Set(FontSet),
+    File(PathBuf),
+}
+
+//  Settings given here win over the ROM database and the configuration file
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    //  A .ch8, .c8, .sc8 or .xo8 ROM, a .hex dump, an Octo .gif cartridge
+    //  or a .zip holding one of them
+    pub rom_path: Option<PathBuf>,
+    pub config_path: Option<PathBuf>,
+    pub database_path: Option<PathBuf>,
+    pub print_keymap: bool,
+    pub decode_cache: bool,
+    pub basic_blocks: bool,
+    pub strict: bool,
+    //  Keeps return addresses in memory at 0xEA0 like the COSMAC VIP
+    pub vip_stack: bool,
+    pub stack_depth: Option<usize>,
+    pub window: WindowSettings,
+    pub palette: Option<Palette>,
+    pub platform: Option<Platform>,
+    pub tickrate: Option<usize>,
+    pub timing: Option<TimingMode>,
+    pub machine_code: Option<MachineCodePolicy>,
+    pub font: Option<FontChoice>,
+    pub font_address: Option<u16>,
+    pub quirks: Vec<(String, bool)>,
+    pub trace_path: Option<PathBuf>,
+    pub trace_format: Option<TraceFormat>,
+    pub reference_trace: Option<PathBuf>,
+    pub reference_format: Option<TraceFormat>,
+    //  Where the profile is written when the program stops; "-" is stdout,
+    //  like for the coverage report
+    pub profile_path: Option<PathBuf>,
+    //  An .html path gets a coverage map, anything else an annotated disassembly
+    pub coverage_path: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn from_args<I: Iterator<Item=String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.peekable();
+        if args.peek().map(String::as_str) == Some("tracediff") {
+            options.command = Command::TraceDiff;
+            args.next();
+        }
+        for arg in args {
+            if arg == "--fullscreen" {
+                options.window.fullscreen = true;
+            } else if arg == "--fixed-size" {
+                options.window.resizable = false;
+            } else if arg == "--print-keymap" {
+                options.print_keymap = true;
+            } else if arg == "--decode-cache" {
+                options.decode_cache = true;
+            } else if arg == "--basic-blocks" {
+                options.basic_blocks = true;
+            } else if arg == "--strict" {
+                options.strict = true;
+            } else if arg == "--vip-stack" {
+                options.vip_stack = true;
+            } else if let Some(mode) = arg.strip_prefix("--scaling=") {
+                options.window.scaling = mode.parse()?;
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                options.config_path = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("--database=") {
+                options.database_path = Some(PathBuf::from(path));
+            } else if let Some(palette) = arg.strip_prefix("--palette=") {
+                options.palette = Some(palette.parse()?);
+            } else if let Some(platform) = arg.strip_prefix("--platform=") {
+                options.platform = Some(platform.parse()?);
+            } else if let Some(tickrate) = arg.strip_prefix("--tickrate=") {
+                let tickrate = tickrate.parse()
+                    .map_err(|_| format!("invalid tickrate '{}'", tickrate))?;
+                options.tickrate = Some(tickrate);
+            } else if let Some(depth) = arg.strip_prefix("--stack-depth=") {
+                let depth = depth.parse()
+                    .map_err(|_| format!("invalid stack depth '{}'", depth))?;
+                options.stack_depth = Some(depth);
+            } else if let Some(timing) = arg.strip_prefix("--timing=") {
+                options.timing = Some(timing.parse()?);
+            } else if let Some(policy) = arg.strip_prefix("--machine-code=") {
+                options.machine_code = Some(policy.parse()?);
+            } else if let Some(font) = arg.strip_prefix("--font=") {
+                options.font = Some(match font.parse() {
+                    Ok(set) => FontChoice::Set(set),
+                    Err(_) => FontChoice::File(PathBuf::from(font)),
+                });
+            } else if let Some(address) = arg.strip_prefix("--font-address=") {
+                let hex = address.strip_prefix("0x").unwrap_or(address);
+                let address = u16::from_str_radix(hex, 16)
+                    .map_err(|_| format!("invalid font address '{}'", address))?;
+                options.font_address = Some(address);
+            } else if let Some(path) = arg.strip_prefix("--trace=") {
+                options.trace_path = Some(PathBuf::from(path));
+            } else if let Some(format) = arg.strip_prefix("--trace-format=") {
+                options.trace_format = Some(format.parse()?);
+            } else if let Some(format) = arg.strip_prefix("--reference-format=") {
+                options.reference_format = Some(format.parse()?);
+            } else if let Some(path) = arg.strip_prefix("--profile=") {
+                options.profile_path = Some(PathBuf::from(path));
+            } else if let Some(path) = arg.strip_prefix("--coverage=") {
+                options.coverage_path = Some(PathBuf::from(path));
+            } else if let Some(quirk) = arg.strip_prefix("--quirk=") {
+                options.quirks.push((quirk.to_string(), true));
+            } else if let Some(quirk) = arg.strip_prefix("--no-quirk=") {
+                options.quirks.push((quirk.to_string(), false));
+            } else if arg.starts_with("--") {
+                return Err(format!("unknown option '{}'", arg));
+            } else if options.rom_path.is_none() {
+                options.rom_path = Some(PathBuf::from(arg));
+            } else if options.command == Command::TraceDiff && options.reference_trace.is_none() {
+                options.reference_trace = Some(PathBuf::from(arg));
+            } else {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+        }
+        if options.command == Command::TraceDiff && options.reference_trace.is_none() {
+            return Err("usage: tracediff [options] <rom> <reference trace>".to_string());
+        }
+        Ok(options)
+    }
+
+    //  Without --trace-format, the format follows the extension of the trace file
+    pub fn trace_format(&self) -> TraceFormat {
+        self.trace_format
+            .or_else(|| self.trace_path.as_deref().and_then(TraceFormat::for_path))
+            .unwrap_or(TraceFormat::Text)
+    }
+
+    //  Without --reference-format, the format follows the extension of the
+    //  reference; it must be machine readable, so the default is CSV
+    pub fn reference_trace_format(&self) -> TraceFormat {
+        self.reference_format
+            .or_else(|| self.reference_trace.as_deref().and_then(TraceFormat::for_path))
+            .unwrap_or(TraceFormat::Csv)
+    }
+
+    pub fn rom_name(&self) -> Option<String> {
+        self.rom_path.as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use crate::frontend::gamepad::GamepadMap;
+use crate::frontend::keymap::{BindingTable, KeyMap};
+
+//  Looked up in the working directory when no --config is given
+pub const DEFAULT_CONFIG_FILE: &str = "chip8.toml";
+
+//  Example:
+//
+//      [keypad]
+//      "5" = ["W", "Up"]
+//
+//      [gamepad]
+//      "5" = ["South", "East"]
+//
+//      [gamepad_profiles.paddles]
+//      "1" = ["DPadUp"]
+//      "4" = ["DPadDown"]
+//
+//      [rom."pong.ch8"]
+//      gamepad_profile = "paddles"
+//
+//      [rom."pong.ch8".keypad]
+//      "1" = ["Q"]
+//      "4" = ["A"]
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub keypad: BindingTable,
+    #[serde(default)]
+    pub gamepad: BindingTable,
+    #[serde(default)]
+    pub gamepad_profiles: BTreeMap<String, BindingTable>,
+    #[serde(default)]
+    pub rom: BTreeMap<String, RomConfig>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RomConfig {
+    #[serde(default)]
+    pub keypad: BindingTable,
+    pub gamepad_profile: Option<String>,
+    #[serde(default)]
+    pub gamepad: BindingTable,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map_err(|e| format!("invalid configuration in {}: {}", path.display(), e))
+    }
+
+    //  `base`, then the [keypad] section, then the section of the ROM
+    //  (matched by file name), each overriding the keys it mentions.
+    pub fn keymap_for(&self, base: KeyMap, rom_name: Option<&str>) -> Result<KeyMap, String> {
+        let mut keymap = base.with_overrides(&self.keypad)?;
+        if let Some(rom_config) = rom_name.and_then(|name| self.rom.get(name)) {
+            keymap = keymap.with_overrides(&rom_config.keypad)
+                .map_err(|e| format!("in keypad overrides for {}: {}", rom_name.unwrap(), e))?;
+        }
+        Ok(keymap)
+    }
+
+    //  `base`, [gamepad], the ROM's profile and finally the ROM's own
+    //  [rom."...".gamepad] section.
+    pub fn gamepad_map_for(&self, base: GamepadMap, rom_name: Option<&str>) -> Result<GamepadMap, String> {
+        let mut gamepad_map = base.with_overrides(&self.gamepad)?;
+        if let Some(rom_config) = rom_name.and_then(|name| self.rom.get(name)) {
+            let rom_name = rom_name.unwrap();
+            if let Some(profile_name) = &rom_config.gamepad_profile {
+                let profile = self.gamepad_profiles.get(profile_name)
+                    .ok_or_else(|| format!("{} uses unknown gamepad profile '{}'", rom_name, profile_name))?;
+                gamepad_map = gamepad_map.with_overrides(profile)
+                    .map_err(|e| format!("in gamepad profile {}: {}", profile_name, e))?;
+            }
+            gamepad_map = gamepad_map.with_overrides(&rom_config.gamepad)
+                .map_err(|e| format!("in gamepad overrides for {}: {}", rom_name, e))?;
+        }
+        Ok(gamepad_map)
+    }
+}
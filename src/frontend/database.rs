@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use crate::cpu::quirks::{Platform, Quirks};
+use crate::frontend::keymap::BindingTable;
+use crate::frontend::window::Palette;
+
+//  A ROM database in the format of the chip-8-database's programs.json
+//  (https://github.com/chip-8/chip-8-database), keyed by the SHA-1 of the ROM.
+//  Entries in a local database take precedence over the bundled ones.
+pub const BUNDLED_DATABASE: &str = include_str!("../../data/programs.json");
+//  Looked up in the working directory when no --database is given
+pub const DEFAULT_DATABASE_FILE: &str = "programs.json";
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub roms: BTreeMap<String, RomEntry>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomEntry {
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    #[serde(default)]
+    pub quirky_platforms: BTreeMap<String, BTreeMap<String, bool>>,
+    pub tickrate: Option<usize>,
+    pub colors: Option<Colors>,
+    #[serde(default)]
+    pub keys: BTreeMap<String, u8>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Colors {
+    #[serde(default)]
+    pub pixels: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    programs: Vec<Program>,
+}
+
+impl Database {
+    pub fn bundled() -> Database {
+        Database::parse(BUNDLED_DATABASE).expect("the bundled ROM database is valid")
+    }
+
+    pub fn parse(json: &str) -> Result<Database, String> {
+        let programs = serde_json::from_str(json)
+            .map_err(|e| format!("invalid ROM database: {}", e))?;
+        Ok(Database { programs })
+    }
+
+    pub fn load(path: &Path) -> Result<Database, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Database::parse(&json).map_err(|e| format!("{} ({})", e, path.display()))
+    }
+
+    //  Entries of `other` shadow the ones already in the database
+    pub fn extend_with(&mut self, other: Database) {
+        let mut programs = other.programs;
+        programs.append(&mut self.programs);
+        self.programs = programs;
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<(&str, &RomEntry)> {
+        self.programs.iter()
+            .find_map(|program| program.roms.get(sha1)
+                .map(|rom| (program.title.as_str(), rom)))
+    }
+}
+
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+impl RomEntry {
+    //  The first platform of the entry this interpreter knows about
+    pub fn platform(&self) -> Option<Platform> {
+        self.platforms.iter().find_map(|id| id.parse().ok())
+    }
+
+    //  The platform defaults, adjusted by the entry's quirkyPlatforms
+    pub fn quirks_for(&self, platform: Platform) -> Result<Quirks, String> {
+        let mut quirks = platform.quirks();
+        if let Some(overrides) = self.quirky_platforms.get(platform.id()) {
+            for (name, &enabled) in overrides {
+                quirks.set(name, enabled)?;
+            }
+        }
+        Ok(quirks)
+    }
+
+    pub fn palette(&self) -> Result<Option<Palette>, String> {
+        let pixels = match &self.colors {
+            Some(colors) if colors.pixels.len() >= 2 => &colors.pixels,
+            _ => return Ok(None),
+        };
+        Ok(Some(Palette {
+            background: Palette::parse_color(&pixels[0])?,
+            foreground: Palette::parse_color(&pixels[1])?,
+        }))
+    }
+
+    //  The database names the keys a game uses ("up", "a", ...); they are
+    //  bound to the arrow keys and space on the keyboard, and to the D-pad
+    //  and face buttons on gamepads.
+    pub fn key_bindings(&self) -> (BindingTable, BindingTable) {
+        let (mut keyboard, mut gamepad) = (BindingTable::new(), BindingTable::new());
+        for (name, &key) in &self.keys {
+            let (host_key, button) = match name.as_str() {
+                "up" => ("Up", "DPadUp"),
+                "down" => ("Down", "DPadDown"),
+                "left" => ("Left", "DPadLeft"),
+                "right" => ("Right", "DPadRight"),
+                "a" => ("Space", "South"),
+                "b" => ("LShift", "East"),
+                _ => continue,
+            };
+            let key_name = format!("{:X}", key & 0x0F);
+            keyboard.entry(key_name.clone()).or_insert_with(Vec::new).push(host_key.to_string());
+            gamepad.entry(key_name).or_insert_with(Vec::new).push(button.to_string());
+        }
+        (keyboard, gamepad)
+    }
+}
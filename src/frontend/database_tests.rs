@@ -0,0 +1,66 @@
+#[cfg(test)]
+use crate::cpu::quirks::{Platform, Quirks};
+#[cfg(test)]
+use crate::frontend::database::{self, Database};
+#[cfg(test)]
+use crate::frontend::keymap::KeyMap;
+
+#[cfg(test)]
+const LOCAL_DATABASE: &str = r##"[
+    {
+        "title": "Paddles",
+        "roms": {
+            "a9993e364706816aba3e25717850c26c9cd0d89d": {
+                "platforms": ["megachip8", "superchip"],
+                "quirkyPlatforms": { "superchip": { "jump": false } },
+                "tickrate": 40,
+                "colors": { "pixels": ["#102030", "#f0e0d0"] },
+                "keys": { "up": 1, "down": 4, "a": 12 }
+            }
+        }
+    }
+]"##;
+
+#[test]
+fn roms_are_identified_by_sha1(){
+    assert_eq!(database::rom_hash(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
+
+#[test]
+fn bundled_database_is_valid(){
+    let database = Database::bundled();
+    assert!(database.lookup("0ebc4b92c6059d6193565644fb00108161d03d23").is_some());
+}
+
+#[test]
+fn local_entries_take_precedence(){
+    let mut database = Database::parse(r#"[
+        { "title": "Shadowed", "roms": { "a9993e364706816aba3e25717850c26c9cd0d89d": {} } }
+    ]"#).unwrap();
+    database.extend_with(Database::parse(LOCAL_DATABASE).unwrap());
+    let (title, _) = database.lookup("a9993e364706816aba3e25717850c26c9cd0d89d").unwrap();
+    assert_eq!(title, "Paddles");
+    assert!(database.lookup("0000000000000000000000000000000000000000").is_none());
+}
+
+#[test]
+fn entry_settings(){
+    let database = Database::parse(LOCAL_DATABASE).unwrap();
+    let (_, entry) = database.lookup(&database::rom_hash(b"abc")).unwrap();
+
+    //  megachip8 is not supported, so the first usable platform is picked
+    assert_eq!(entry.platform(), Some(Platform::SuperChip));
+    let quirks = entry.quirks_for(Platform::SuperChip).unwrap();
+    assert_eq!(quirks, Quirks { jump: false, ..Platform::SuperChip.quirks() });
+    assert_eq!(entry.tickrate, Some(40));
+
+    let palette = entry.palette().unwrap().unwrap();
+    assert_eq!(palette.background, [0x10, 0x20, 0x30]);
+    assert_eq!(palette.foreground, [0xF0, 0xE0, 0xD0]);
+
+    let (keyboard, _) = entry.key_bindings();
+    let keymap = KeyMap::default().with_additions(&keyboard).unwrap();
+    assert_eq!(keymap.key_for(ggez::event::KeyCode::Up), Some(0x1));
+    assert_eq!(keymap.key_for(ggez::event::KeyCode::Key1), Some(0x1));
+    assert_eq!(keymap.key_for(ggez::event::KeyCode::Space), Some(0xC));
+}
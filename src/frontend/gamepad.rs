@@ -0,0 +1,74 @@
+use ggez::input::gamepad::gilrs::{Axis, Button};
+use crate::frontend::keymap::{Bindings, HostInput};
+
+const BUTTON_NAMES: [(&str, Button); 19] = [
+    ("South", Button::South), ("East", Button::East),
+    ("North", Button::North), ("West", Button::West),
+    ("C", Button::C), ("Z", Button::Z),
+    ("LeftTrigger", Button::LeftTrigger), ("LeftTrigger2", Button::LeftTrigger2),
+    ("RightTrigger", Button::RightTrigger), ("RightTrigger2", Button::RightTrigger2),
+    ("Select", Button::Select), ("Start", Button::Start), ("Mode", Button::Mode),
+    ("LeftThumb", Button::LeftThumb), ("RightThumb", Button::RightThumb),
+    ("DPadUp", Button::DPadUp), ("DPadDown", Button::DPadDown),
+    ("DPadLeft", Button::DPadLeft), ("DPadRight", Button::DPadRight),
+];
+
+//  How far a stick has to be pushed before it counts as a D-pad press
+const STICK_THRESHOLD: f32 = 0.5;
+
+impl HostInput for Button {
+    const KIND: &'static str = "button";
+
+    fn parse(name: &str) -> Option<Button> {
+        BUTTON_NAMES.iter()
+            .find(|(button_name, _)| button_name.eq_ignore_ascii_case(name))
+            .map(|&(_, button)| button)
+    }
+}
+
+pub type GamepadMap = Bindings<Button>;
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        //  Most games move with 2/4/6/8 and act with 5
+        Bindings::from_layout(&[
+            (0x2, Button::DPadUp), (0x4, Button::DPadLeft),
+            (0x6, Button::DPadRight), (0x8, Button::DPadDown),
+            (0x5, Button::South),
+        ])
+    }
+}
+
+//  Turns the left stick into D-pad presses, so that profiles only ever have
+//  to deal with buttons.
+#[derive(Clone, Debug, Default)]
+pub struct StickState {
+    horizontal: Option<Button>,
+    vertical: Option<Button>,
+}
+
+impl StickState {
+    //  Returns the D-pad button released and the one pressed by the move, if any
+    pub fn update(&mut self, axis: Axis, value: f32) -> (Option<Button>, Option<Button>) {
+        let (current, negative, positive) = match axis {
+            Axis::LeftStickX => (&mut self.horizontal, Button::DPadLeft, Button::DPadRight),
+            //  gilrs reports up as positive
+            Axis::LeftStickY => (&mut self.vertical, Button::DPadDown, Button::DPadUp),
+            _ => return (None, None),
+        };
+
+        let direction = if value <= -STICK_THRESHOLD {
+            Some(negative)
+        } else if value >= STICK_THRESHOLD {
+            Some(positive)
+        } else {
+            None
+        };
+
+        if direction == *current {
+            return (None, None);
+        }
+        let released = std::mem::replace(current, direction);
+        (released, direction)
+    }
+}
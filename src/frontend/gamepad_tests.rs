@@ -0,0 +1,62 @@
+#[cfg(test)]
+use ggez::input::gamepad::gilrs::{Axis, Button};
+#[cfg(test)]
+use crate::frontend::config::Config;
+#[cfg(test)]
+use crate::frontend::gamepad::{GamepadMap, StickState};
+
+#[test]
+fn dpad_moves_by_default(){
+    let gamepad = GamepadMap::default();
+    assert_eq!(gamepad.key_for(Button::DPadUp), Some(0x2));
+    assert_eq!(gamepad.key_for(Button::DPadLeft), Some(0x4));
+    assert_eq!(gamepad.key_for(Button::DPadRight), Some(0x6));
+    assert_eq!(gamepad.key_for(Button::DPadDown), Some(0x8));
+}
+
+#[test]
+fn rom_profiles_and_overrides(){
+    let config: Config = toml::from_str(r#"
+        [gamepad]
+        "5" = ["South", "East"]
+
+        [gamepad_profiles.paddles]
+        "1" = ["DPadUp"]
+        "4" = ["DPadDown"]
+
+        [rom."pong.ch8"]
+        gamepad_profile = "paddles"
+
+        [rom."pong.ch8".gamepad]
+        "C" = ["Start"]
+    "#).unwrap();
+    let gamepad = config.gamepad_map_for(GamepadMap::default(), Some("pong.ch8")).unwrap();
+    assert_eq!(gamepad.key_for(Button::DPadUp), Some(0x1));
+    assert_eq!(gamepad.key_for(Button::DPadDown), Some(0x4));
+    assert_eq!(gamepad.key_for(Button::East), Some(0x5));
+    assert_eq!(gamepad.key_for(Button::Start), Some(0xC));
+    assert!(gamepad.host_inputs(0x2).is_empty());
+
+    let gamepad = config.gamepad_map_for(GamepadMap::default(), Some("brix.ch8")).unwrap();
+    assert_eq!(gamepad.key_for(Button::DPadUp), Some(0x2));
+}
+
+#[test]
+fn unknown_profile_is_an_error(){
+    let config: Config = toml::from_str(r#"
+        [rom."pong.ch8"]
+        gamepad_profile = "missing"
+    "#).unwrap();
+    assert!(config.gamepad_map_for(GamepadMap::default(), Some("pong.ch8")).is_err());
+}
+
+#[test]
+fn stick_acts_as_dpad(){
+    let mut stick = StickState::default();
+    assert_eq!(stick.update(Axis::LeftStickX, 0.2), (None, None));
+    assert_eq!(stick.update(Axis::LeftStickX, 0.9), (None, Some(Button::DPadRight)));
+    assert_eq!(stick.update(Axis::LeftStickX, 0.8), (None, None));
+    assert_eq!(stick.update(Axis::LeftStickX, -0.7), (Some(Button::DPadRight), Some(Button::DPadLeft)));
+    assert_eq!(stick.update(Axis::LeftStickY, 1.0), (None, Some(Button::DPadUp)));
+    assert_eq!(stick.update(Axis::LeftStickX, 0.0), (Some(Button::DPadLeft), None));
+}
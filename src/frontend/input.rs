@@ -0,0 +1,101 @@
+use ggez::event::KeyCode;
+use ggez::input::gamepad::gilrs::{Button, EventType, GamepadId};
+use crate::frontend::gamepad::{GamepadMap, StickState};
+use crate::frontend::keymap::KeyMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Keyboard(KeyCode),
+    Gamepad(GamepadId, Button),
+}
+
+//  Keyboard and gamepads all feed the same CHIP-8 keypad. A key stays down
+//  as long as any host input bound to it is held, so releasing the button
+//  of one device does not let go of a key still held on another.
+//
+//  Every method returns the keypad changes to forward to the CPU, as
+//  (key, pressed) pairs.
+#[derive(Clone, Debug, Default)]
+pub struct InputState {
+    held: Vec<(Source, u8)>,
+    sticks: Vec<(GamepadId, StickState)>,
+}
+
+impl InputState {
+    pub fn key_pressed(&mut self, keycode: KeyCode, keymap: &KeyMap) -> Option<(u8, bool)> {
+        let key = keymap.key_for(keycode)?;
+        self.press(Source::Keyboard(keycode), key)
+    }
+
+    pub fn key_released(&mut self, keycode: KeyCode) -> Option<(u8, bool)> {
+        self.release(Source::Keyboard(keycode))
+    }
+
+    pub fn gamepad_event(&mut self, id: GamepadId, event: EventType, gamepad: &GamepadMap) -> Vec<(u8, bool)> {
+        let (released, pressed) = match event {
+            EventType::ButtonPressed(button, _) => (None, Some(button)),
+            EventType::ButtonReleased(button, _) => (Some(button), None),
+            EventType::AxisChanged(axis, value, _) => self.stick(id).update(axis, value),
+            //  Whatever was held on a pad that went away is let go
+            EventType::Disconnected => {
+                let buttons = self.held.iter()
+                    .filter_map(|&(source, _)| match source {
+                        Source::Gamepad(held_id, button) if held_id == id => Some(button),
+                        _ => None,
+                    })
+                    .collect::<Vec<Button>>();
+                return buttons.into_iter()
+                    .filter_map(|button| self.release(Source::Gamepad(id, button)))
+                    .collect();
+            }
+            _ => (None, None),
+        };
+
+        let mut changes = Vec::new();
+        if let Some(button) = released {
+            changes.extend(self.release(Source::Gamepad(id, button)));
+        }
+        if let Some(button) = pressed {
+            if let Some(key) = gamepad.key_for(button) {
+                changes.extend(self.press(Source::Gamepad(id, button), key));
+            }
+        }
+        changes
+    }
+
+    fn press(&mut self, source: Source, key: u8) -> Option<(u8, bool)> {
+        if self.held.iter().any(|&(held_source, _)| held_source == source) {
+            //  Keyboard auto-repeat
+            return None;
+        }
+        let was_down = self.is_down(key);
+        self.held.push((source, key));
+        match was_down {
+            true => None,
+            false => Some((key, true)),
+        }
+    }
+
+    fn release(&mut self, source: Source) -> Option<(u8, bool)> {
+        let position = self.held.iter().position(|&(held_source, _)| held_source == source)?;
+        let (_, key) = self.held.remove(position);
+        match self.is_down(key) {
+            true => None,
+            false => Some((key, false)),
+        }
+    }
+
+    fn is_down(&self, key: u8) -> bool {
+        self.held.iter().any(|&(_, held_key)| held_key == key)
+    }
+
+    fn stick(&mut self, id: GamepadId) -> &mut StickState {
+        match self.sticks.iter().position(|(stick_id, _)| *stick_id == id) {
+            Some(position) => &mut self.sticks[position].1,
+            None => {
+                self.sticks.push((id, StickState::default()));
+                &mut self.sticks.last_mut().unwrap().1
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use ggez::event::KeyCode;
+
+//  Host keys that can be named in a configuration file; the names are the
+//  ones ggez uses, e.g. "Key1", "Q", "Numpad7", "Up".
+const KEY_NAMES: [(&str, KeyCode); 78] = [
+    ("Key0", KeyCode::Key0), ("Key1", KeyCode::Key1), ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3), ("Key4", KeyCode::Key4), ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6), ("Key7", KeyCode::Key7), ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("A", KeyCode::A), ("B", KeyCode::B), ("C", KeyCode::C), ("D", KeyCode::D),
+    ("E", KeyCode::E), ("F", KeyCode::F), ("G", KeyCode::G), ("H", KeyCode::H),
+    ("I", KeyCode::I), ("J", KeyCode::J), ("K", KeyCode::K), ("L", KeyCode::L),
+    ("M", KeyCode::M), ("N", KeyCode::N), ("O", KeyCode::O), ("P", KeyCode::P),
+    ("Q", KeyCode::Q), ("R", KeyCode::R), ("S", KeyCode::S), ("T", KeyCode::T),
+    ("U", KeyCode::U), ("V", KeyCode::V), ("W", KeyCode::W), ("X", KeyCode::X),
+    ("Y", KeyCode::Y), ("Z", KeyCode::Z),
+    ("Numpad0", KeyCode::Numpad0), ("Numpad1", KeyCode::Numpad1), ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3), ("Numpad4", KeyCode::Numpad4), ("Numpad5", KeyCode::Numpad5),
+    ("Numpad6", KeyCode::Numpad6), ("Numpad7", KeyCode::Numpad7), ("Numpad8", KeyCode::Numpad8),
+    ("Numpad9", KeyCode::Numpad9), ("Add", KeyCode::Add), ("Subtract", KeyCode::Subtract),
+    ("Multiply", KeyCode::Multiply), ("Divide", KeyCode::Divide), ("Decimal", KeyCode::Decimal),
+    ("NumpadEnter", KeyCode::NumpadEnter),
+    ("Up", KeyCode::Up), ("Down", KeyCode::Down), ("Left", KeyCode::Left), ("Right", KeyCode::Right),
+    ("Space", KeyCode::Space), ("Return", KeyCode::Return), ("Tab", KeyCode::Tab),
+    ("Back", KeyCode::Back), ("Insert", KeyCode::Insert), ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home), ("End", KeyCode::End), ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("LShift", KeyCode::LShift), ("RShift", KeyCode::RShift),
+    ("LControl", KeyCode::LControl), ("RControl", KeyCode::RControl),
+    ("LAlt", KeyCode::LAlt), ("RAlt", KeyCode::RAlt),
+    ("Comma", KeyCode::Comma), ("Period", KeyCode::Period), ("Slash", KeyCode::Slash),
+    ("Semicolon", KeyCode::Semicolon), ("Apostrophe", KeyCode::Apostrophe),
+    ("Minus", KeyCode::Minus),
+];
+
+//  Something on the host side that can be bound to a CHIP-8 key
+pub trait HostInput: Copy + PartialEq + fmt::Debug {
+    //  What the input is called in error messages, e.g. "key"
+    const KIND: &'static str;
+
+    fn parse(name: &str) -> Option<Self>;
+}
+
+impl HostInput for KeyCode {
+    const KIND: &'static str = "key";
+
+    fn parse(name: &str) -> Option<KeyCode> {
+        KEY_NAMES.iter()
+            .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+            .map(|&(_, keycode)| keycode)
+    }
+}
+
+//  A CHIP-8 key is written as a single hexadecimal digit, e.g. "A" or "0xA"
+pub fn parse_chip8_key(name: &str) -> Option<u8> {
+    let digit = name.strip_prefix("0x").unwrap_or(name);
+    match u8::from_str_radix(digit, 16) {
+        Ok(key) if key < 16 && digit.len() == 1 => Some(key),
+        _ => None,
+    }
+}
+
+//  Keypad bindings as read from a configuration file: CHIP-8 key -> host input names
+pub type BindingTable = BTreeMap<String, Vec<String>>;
+
+//  Which host inputs press each of the 16 CHIP-8 keys; more than one input
+//  may be bound to the same key, but an input presses at most one key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings<T: HostInput> {
+    bindings: [Vec<T>; 16],
+}
+
+pub type KeyMap = Bindings<KeyCode>;
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        //  The classic layout: the left side of a QWERTY keyboard
+        //      1 2 3 C         1 2 3 4
+        //      4 5 6 D   <->   Q W E R
+        //      7 8 9 E         A S D F
+        //      A 0 B F         Z X C V
+        let layout = [KeyCode::X,
+            KeyCode::Key1, KeyCode::Key2, KeyCode::Key3,
+            KeyCode::Q, KeyCode::W, KeyCode::E,
+            KeyCode::A, KeyCode::S, KeyCode::D,
+            KeyCode::Z, KeyCode::C, KeyCode::Key4,
+            KeyCode::R, KeyCode::F, KeyCode::V];
+        let mut bindings: [Vec<KeyCode>; 16] = Default::default();
+        for (key, &keycode) in layout.iter().enumerate() {
+            bindings[key].push(keycode);
+        }
+        Bindings { bindings }
+    }
+}
+
+impl<T: HostInput> Bindings<T> {
+    pub fn from_layout(layout: &[(u8, T)]) -> Bindings<T> {
+        let mut bindings: [Vec<T>; 16] = Default::default();
+        for &(key, input) in layout {
+            bindings[key as usize].push(input);
+        }
+        Bindings { bindings }
+    }
+
+    //  Rebinds every CHIP-8 key mentioned in `table`, leaving the others as
+    //  they are. Host keys taken by the new bindings are released from
+    //  whatever key they were bound to before, so that a per-ROM override
+    //  can reuse keys of the default layout.
+    pub fn with_overrides(&self, table: &BindingTable) -> Result<Bindings<T>, String> {
+        self.apply(table, true)
+    }
+
+    //  Like with_overrides, but keeps the inputs already bound to the keys
+    pub fn with_additions(&self, table: &BindingTable) -> Result<Bindings<T>, String> {
+        self.apply(table, false)
+    }
+
+    fn apply(&self, table: &BindingTable, replace: bool) -> Result<Bindings<T>, String> {
+        let mut changes: Vec<(u8, Vec<T>)> = Vec::new();
+        for (key_name, host_names) in table {
+            let key = parse_chip8_key(key_name)
+                .ok_or_else(|| format!("'{}' is not a CHIP-8 key (expected 0-F)", key_name))?;
+            let mut inputs = Vec::new();
+            for host_name in host_names {
+                let input = T::parse(host_name)
+                    .ok_or_else(|| format!("unknown host {} '{}' for CHIP-8 key {:X}", T::KIND, host_name, key))?;
+                inputs.push(input);
+            }
+            changes.push((key, inputs));
+        }
+
+        let mut bindings = self.clone();
+        for (key, inputs) in &changes {
+            if replace {
+                bindings.bindings[*key as usize].clear();
+            }
+            for binding in bindings.bindings.iter_mut() {
+                binding.retain(|input| !inputs.contains(input));
+            }
+        }
+        for (key, inputs) in changes {
+            bindings.bindings[key as usize].extend(inputs);
+        }
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen: Vec<(T, usize)> = Vec::new();
+        for (key, binding) in self.bindings.iter().enumerate() {
+            for &input in binding {
+                if let Some(&(_, owner)) = seen.iter().find(|&&(seen_input, _)| seen_input == input) {
+                    return Err(format!("host {} {:?} is bound to both CHIP-8 key {:X} and {:X}",
+                                       T::KIND, input, owner, key));
+                }
+                seen.push((input, key));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn key_for(&self, input: T) -> Option<u8> {
+        self.bindings.iter()
+            .position(|binding| binding.contains(&input))
+            .map(|key| key as u8)
+    }
+
+    pub fn host_inputs(&self, key: u8) -> &[T] {
+        &self.bindings[key as usize]
+    }
+}
+
+impl<T: HostInput> fmt::Display for Bindings<T> {
+    //  Prints the bindings laid out like the COSMAC VIP hex keypad
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keypad_rows = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD],
+                           [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+        for row in keypad_rows.iter() {
+            for &key in row.iter() {
+                let names = self.host_inputs(key).iter()
+                    .map(|input| format!("{:?}", input))
+                    .collect::<Vec<String>>()
+                    .join("/");
+                write!(f, "{:X}: {:<16}", key, names)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+use ggez::event::KeyCode;
+#[cfg(test)]
+use crate::frontend::config::Config;
+#[cfg(test)]
+use crate::frontend::input::InputState;
+#[cfg(test)]
+use crate::frontend::keymap::KeyMap;
+
+#[test]
+fn default_layout(){
+    let keymap = KeyMap::default();
+    assert_eq!(keymap.key_for(KeyCode::X), Some(0x0));
+    assert_eq!(keymap.key_for(KeyCode::Key4), Some(0xC));
+    assert_eq!(keymap.key_for(KeyCode::V), Some(0xF));
+    assert_eq!(keymap.key_for(KeyCode::Up), None);
+}
+
+#[test]
+fn multiple_host_inputs_per_chip8_key(){
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "5" = ["W", "Up"]
+        "0x8" = ["S", "Down"]
+    "#).unwrap();
+    let keymap = config.keymap_for(KeyMap::default(), None).unwrap();
+    assert_eq!(keymap.key_for(KeyCode::W), Some(0x5));
+    assert_eq!(keymap.key_for(KeyCode::Up), Some(0x5));
+    assert_eq!(keymap.key_for(KeyCode::Down), Some(0x8));
+}
+
+#[test]
+fn per_rom_overrides_take_precedence(){
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "1" = ["Key1", "Numpad1"]
+
+        [rom."pong.ch8".keypad]
+        "1" = ["Q"]
+        "4" = ["A"]
+    "#).unwrap();
+    let keymap = config.keymap_for(KeyMap::default(), Some("pong.ch8")).unwrap();
+    assert_eq!(keymap.key_for(KeyCode::Q), Some(0x1));
+    assert_eq!(keymap.key_for(KeyCode::A), Some(0x4));
+    //  Q and A were released by 4 and 7, Key1 by 1
+    assert_eq!(keymap.key_for(KeyCode::Key1), None);
+    assert!(keymap.host_inputs(0x7).is_empty());
+
+    let keymap = config.keymap_for(KeyMap::default(), Some("tetris.ch8")).unwrap();
+    assert_eq!(keymap.key_for(KeyCode::Numpad1), Some(0x1));
+    assert_eq!(keymap.key_for(KeyCode::Q), Some(0x4));
+}
+
+#[test]
+fn duplicate_bindings_are_rejected(){
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "1" = ["Up"]
+        "2" = ["Up"]
+    "#).unwrap();
+    assert!(config.keymap_for(KeyMap::default(), None).is_err());
+}
+
+#[test]
+fn unknown_keys_are_rejected(){
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "G" = ["Q"]
+    "#).unwrap();
+    assert!(config.keymap_for(KeyMap::default(), None).is_err());
+
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "1" = ["NotAKey"]
+    "#).unwrap();
+    assert!(config.keymap_for(KeyMap::default(), None).is_err());
+}
+
+#[test]
+fn key_stays_down_while_any_binding_is_held(){
+    let config: Config = toml::from_str(r#"
+        [keypad]
+        "5" = ["W", "Up"]
+    "#).unwrap();
+    let keymap = config.keymap_for(KeyMap::default(), None).unwrap();
+    let mut input = InputState::default();
+    assert_eq!(input.key_pressed(KeyCode::W, &keymap), Some((0x5, true)));
+    assert_eq!(input.key_pressed(KeyCode::W, &keymap), None);
+    assert_eq!(input.key_pressed(KeyCode::Up, &keymap), None);
+    assert_eq!(input.key_released(KeyCode::W), None);
+    assert_eq!(input.key_released(KeyCode::Up), Some((0x5, false)));
+    assert_eq!(input.key_pressed(KeyCode::Space, &keymap), None);
+}
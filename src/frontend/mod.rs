@@ -0,0 +1,139 @@
+use ggez::{conf, event, graphics, Context, ContextBuilder};
+use ggez::conf::WindowSetup;
+use ggez::event::winit_event::{ElementState, Event, KeyboardInput, WindowEvent};
+use ggez::event::KeyCode;
+use ggez::input::gamepad::gilrs;
+use ggez::input::keyboard;
+use crate::Machine;
+use crate::frontend::gamepad::GamepadMap;
+use crate::frontend::input::InputState;
+use crate::frontend::keymap::KeyMap;
+use crate::frontend::screen::Screen;
+use crate::frontend::window::WindowSettings;
+
+pub mod cli;
+pub mod config;
+pub mod database;
+pub mod gamepad;
+pub mod input;
+pub mod keymap;
+pub mod octo;
+pub mod rom;
+pub mod screen;
+pub mod window;
+mod database_tests;
+mod gamepad_tests;
+mod keymap_tests;
+mod octo_tests;
+mod rom_tests;
+mod window_tests;
+
+//  Everything the windowed run loop needs to know about the host side
+#[derive(Clone, Debug, Default)]
+pub struct FrontendSettings {
+    pub window: WindowSettings,
+    pub keymap: KeyMap,
+    pub gamepad: GamepadMap,
+}
+
+//  Runs `machine` in a window at 60 frames per second until the program
+//  halts or the window is closed
+pub fn run(machine: &mut Machine, settings: &FrontendSettings) {
+    let configuration = conf::Conf {
+        window_mode: settings.window.window_mode(),
+        window_setup: WindowSetup::default().title("CHIP-8 Emulator").vsync(true),
+        ..Default::default()
+    };
+    let (mut ctx, mut event_loop) = ContextBuilder::new("CHIP-8 Emulator", "")
+        .conf(configuration)
+        .build()
+        .unwrap();
+
+    let mut screen = Screen::new(&settings.window);
+    let mut input = InputState::default();
+
+    graphics::clear(&mut ctx, graphics::Color::from_rgb(0, 0, 0));
+
+    while ctx.continuing {
+        ctx.timer_context.tick();
+        event_loop.poll_events(|event| {
+                ctx.process_event(&event);
+                handle_event(&mut ctx, event, machine, &mut screen, settings, &mut input);
+            }
+        );
+
+        while let Some(gilrs::Event { id, event, .. }) = ctx.gamepad_context.next_event() {
+            for (key, pressed) in input.gamepad_event(id, event, &settings.gamepad) {
+                machine.set_key(key, pressed);
+            }
+        }
+
+        while ggez::timer::check_update_time(&mut ctx, 60) {
+            //  A program that halted with 0000 leaves its last frame up until
+            //  the window is closed; one that failed ends the run so that the
+            //  error gets reported
+            if machine.is_halted() {
+                if machine.error().is_some() {
+                    event::quit(&mut ctx);
+                }
+                break;
+            }
+            machine.run_frame();
+        }
+
+        if machine.take_display_dirty() || screen.needs_redraw() {
+            screen.draw(&mut ctx, machine.framebuffer()).unwrap();
+        }
+    }
+}
+
+fn handle_event(ctx: &mut Context, event: Event, machine: &mut Machine, screen: &mut Screen,
+                settings: &FrontendSettings, input: &mut InputState) {
+    if let Event::WindowEvent { event, .. } = event {
+        match event {
+            WindowEvent::CloseRequested => event::quit(ctx),
+
+            WindowEvent::Resized(logical_size) => {
+                if let Err(e) = screen.resize(ctx, logical_size.width as f32, logical_size.height as f32) {
+                    eprintln!("cannot resize the display: {}", e);
+                }
+            }
+
+            WindowEvent::KeyboardInput {
+                input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(current_keycode),
+                    ..
+                },
+                ..
+            } => {
+                if current_keycode == KeyCode::F11 && !keyboard::is_key_repeated(ctx) {
+                    if let Err(e) = screen.toggle_fullscreen(ctx) {
+                        eprintln!("cannot toggle fullscreen: {}", e);
+                    }
+                }
+
+                if let Some((key, pressed)) = input.key_pressed(current_keycode, &settings.keymap) {
+                    machine.set_key(key, pressed);
+                }
+            }
+
+            WindowEvent::KeyboardInput {
+                input:
+                KeyboardInput {
+                    state: ElementState::Released,
+                    virtual_keycode: Some(current_keycode),
+                    ..
+                },
+                ..
+            } => {
+                if let Some((key, pressed)) = input.key_released(current_keycode) {
+                    machine.set_key(key, pressed);
+                }
+            }
+
+            _ => (),
+        }
+    }
+}
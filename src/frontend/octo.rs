@@ -0,0 +1,745 @@
+use std::collections::HashMap;
+
+//  Where Octo programs are loaded
+const START: usize = 0x200;
+//  Octo reserves VF for the comparisons it builds out of subtractions
+const COMPARE_TEMP: u8 = 0xF;
+//  Guards against macros that expand into themselves
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+//  How a label used before it is defined gets written once it is
+#[derive(Clone, Copy, Debug)]
+enum Patch {
+    //  The NNN of an instruction
+    Address,
+    //  Two bytes, for `i := long` and `:pointer`
+    Long,
+    //  The low nibble of a byte, for the high bits `:unpack` loads
+    HighNibble,
+    //  A byte, for the low bits `:unpack` loads
+    LowByte,
+}
+
+struct Fixup {
+    address: usize,
+    name: String,
+    patch: Patch,
+    line: usize,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+    None,
+}
+
+//  Octo assembly as Octo cartridges carry it: the CHIP-8, SUPER-CHIP and
+//  XO-CHIP instructions, labels, constants, aliases, structured control
+//  flow, macros and :calc. Strings and :stringmode are not supported.
+//  Returns the bytes to load at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler::new(tokenize(source));
+    assembler.run().map_err(|e| format!("line {}: {}", assembler.line, e))?;
+    Ok(assembler.rom)
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        tokens.extend(line.split_whitespace().map(|text| Token { text: text.to_string(), line: number + 1 }));
+    }
+    tokens
+}
+
+fn number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn register(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [b'v' | b'V', digit] => (*digit as char).to_digit(16).map(|digit| digit as u8),
+        _ => None,
+    }
+}
+
+struct Assembler {
+    tokens: Vec<Token>,
+    position: usize,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    //  Whether 0x200 still holds the room for the jump to main
+    main_slot: bool,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    fixups: Vec<Fixup>,
+    //  Jumps out of the open `if … begin` blocks, waiting for `else` or `end`
+    branches: Vec<usize>,
+    //  The start of every open `loop` and the jumps its `while`s make out of it
+    loops: Vec<(usize, Vec<usize>)>,
+}
+
+impl Assembler {
+    fn new(tokens: Vec<Token>) -> Assembler {
+        Assembler {
+            tokens,
+            position: 0,
+            line: 0,
+            rom: vec![0, 0],
+            here: START + 2,
+            main_slot: true,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            expansions: 0,
+            fixups: Vec::new(),
+            branches: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        while let Some(token) = self.next_token() {
+            self.statement(&token)?;
+        }
+        if !self.branches.is_empty() {
+            return Err("'begin' without 'end'".to_string());
+        }
+        if !self.loops.is_empty() {
+            return Err("'loop' without 'again'".to_string());
+        }
+        if self.main_slot {
+            let main = *self.labels.get("main").ok_or("the program has no 'main' label")?;
+            self.write(START, &[0x10 | (main >> 8) as u8, main as u8]);
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let value = *self.labels.get(&fixup.name).ok_or(format!("undefined name '{}'", fixup.name))?;
+            self.patch(fixup.address, value, fixup.patch)?;
+        }
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position)?.clone();
+        self.position += 1;
+        self.line = token.line;
+        Some(token)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        self.next_token().map(|token| token.text).ok_or_else(|| "unexpected end of the program".to_string())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected '{}', found '{}'", expected, token)),
+        }
+    }
+
+    fn statement(&mut self, token: &Token) -> Result<(), String> {
+        let text = token.text.as_str();
+        match text {
+            ":" => {
+                let name = self.next()?;
+                if name == "main" && self.main_slot && self.here == START + 2 && self.rom.len() == 2 {
+                    //  Nothing to jump over
+                    self.rom.clear();
+                    self.here = START;
+                    self.main_slot = false;
+                }
+                self.define_label(name, self.here)?;
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let token = self.next()?;
+                let value = self.value(&token)?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let token = self.next()?;
+                let register = self.register(&token)?;
+                self.aliases.insert(name, register);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":org" => {
+                let token = self.next()?;
+                let address = self.value(&token)? as i64;
+                if !(START as i64..=0xFFFF).contains(&address) {
+                    return Err(format!("cannot assemble at {:X}", address));
+                }
+                self.here = address as usize;
+            }
+            ":byte" => {
+                let byte = match self.peek() {
+                    Some("{") => Self::to_byte(self.calc()?)?,
+                    _ => {
+                        let token = self.next()?;
+                        self.byte(&token)?
+                    }
+                };
+                self.emit(&[byte]);
+            }
+            ":pointer" => {
+                let token = self.next()?;
+                self.emit_address(&token, &[0, 0], Patch::Long)?;
+            }
+            ":call" => {
+                let token = self.next()?;
+                self.emit_address(&token, &[0x20, 0x00], Patch::Address)?;
+            }
+            ":unpack" => {
+                let token = self.next()?;
+                let nibble = self.value(&token)? as u8 & 0x0F;
+                let label = self.next()?;
+                self.emit_address(&label, &[0x60, nibble << 4], Patch::HighNibble)?;
+                self.emit_address(&label, &[0x61, 0x00], Patch::LowByte)?;
+            }
+            ":macro" => self.define_macro()?,
+            ":assert" => {
+                if self.peek().is_some_and(|token| token != "{") {
+                    self.next()?;
+                }
+                if self.calc()? == 0.0 {
+                    return Err("assertion failed".to_string());
+                }
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit(&[0x00, 0xE0]),
+            "return" | ";" => self.emit(&[0x00, 0xEE]),
+            "exit" => self.emit(&[0x00, 0xFD]),
+            "lores" => self.emit(&[0x00, 0xFE]),
+            "hires" => self.emit(&[0x00, 0xFF]),
+            "scroll-right" => self.emit(&[0x00, 0xFB]),
+            "scroll-left" => self.emit(&[0x00, 0xFC]),
+            "scroll-down" | "scroll-up" => {
+                let token = self.next()?;
+                let rows = self.byte(&token)? & 0x0F;
+                let base = if text == "scroll-down" { 0xC0 } else { 0xD0 };
+                self.emit(&[0x00, base | rows]);
+            }
+            "audio" => self.emit(&[0xF0, 0x02]),
+            "plane" => {
+                let token = self.next()?;
+                let planes = self.byte(&token)? & 0x0F;
+                self.emit(&[0xF0 | planes, 0x01]);
+            }
+            "jump" => {
+                let token = self.next()?;
+                self.emit_address(&token, &[0x10, 0x00], Patch::Address)?;
+            }
+            "jump0" => {
+                let token = self.next()?;
+                self.emit_address(&token, &[0xB0, 0x00], Patch::Address)?;
+            }
+            "native" => {
+                let token = self.next()?;
+                self.emit_address(&token, &[0x00, 0x00], Patch::Address)?;
+            }
+            "sprite" => {
+                let (x, y) = (self.next()?, self.next()?);
+                let (x, y) = (self.register(&x)?, self.register(&y)?);
+                let token = self.next()?;
+                let rows = self.byte(&token)? & 0x0F;
+                self.emit(&[0xD0 | x, y << 4 | rows]);
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let token = self.next()?;
+                let x = self.register(&token)?;
+                let low = match text {
+                    "bcd" => 0x33,
+                    "saveflags" => 0x75,
+                    _ => 0x85,
+                };
+                self.emit(&[0xF0 | x, low]);
+            }
+            "save" | "load" => {
+                let token = self.next()?;
+                let x = self.register(&token)?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let token = self.next()?;
+                    let y = self.register(&token)?;
+                    let low = if text == "save" { 0x02 } else { 0x03 };
+                    self.emit(&[0x50 | x, y << 4 | low]);
+                } else {
+                    self.emit(&[0xF0 | x, if text == "save" { 0x55 } else { 0x65 }]);
+                }
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let token = self.next()?;
+                let x = self.register(&token)?;
+                let low = match text {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(&[0xF0 | x, low]);
+            }
+            "i" => self.pointer_statement()?,
+            "if" => self.if_statement()?,
+            "else" => {
+                let branch = self.branches.pop().ok_or("'else' without 'begin'")?;
+                self.branches.push(self.here);
+                self.emit(&[0x10, 0x00]);
+                self.jump_here(branch);
+            }
+            "end" => {
+                let branch = self.branches.pop().ok_or("'end' without 'begin'")?;
+                self.jump_here(branch);
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err("'while' outside a loop".to_string());
+                }
+                self.condition(true)?;
+                self.loops.last_mut().unwrap().1.push(self.here);
+                self.emit(&[0x10, 0x00]);
+            }
+            "again" => {
+                let (start, exits) = self.loops.pop().ok_or("'again' without 'loop'")?;
+                self.emit(&[0x10 | (start >> 8) as u8 & 0x0F, start as u8]);
+                for exit in exits {
+                    self.jump_here(exit);
+                }
+            }
+            _ if self.is_register(text) => {
+                let x = self.register(text)?;
+                self.register_statement(x)?;
+            }
+            _ if self.macros.contains_key(text) => self.expand(text)?,
+            //  Numbers on their own are data, like sprites
+            _ if number(text).is_some() => {
+                let byte = self.byte(text)?;
+                self.emit(&[byte]);
+            }
+            _ if text.starts_with(':') => return Err(format!("unknown directive '{}'", text)),
+            //  A bare name calls the subroutine it labels
+            _ => self.emit_address(text, &[0x20, 0x00], Patch::Address)?,
+        }
+        Ok(())
+    }
+
+    fn pointer_statement(&mut self) -> Result<(), String> {
+        match self.next()?.as_str() {
+            ":=" => {
+                let token = self.next()?;
+                match token.as_str() {
+                    "hex" | "bighex" => {
+                        let register = self.next()?;
+                        let x = self.register(&register)?;
+                        self.emit(&[0xF0 | x, if token == "hex" { 0x29 } else { 0x30 }]);
+                    }
+                    "long" => {
+                        self.emit(&[0xF0, 0x00]);
+                        let token = self.next()?;
+                        self.emit_address(&token, &[0, 0], Patch::Long)?;
+                    }
+                    _ => self.emit_address(&token, &[0xA0, 0x00], Patch::Address)?,
+                }
+            }
+            "+=" => {
+                let token = self.next()?;
+                let x = self.register(&token)?;
+                self.emit(&[0xF0 | x, 0x1E]);
+            }
+            operator => return Err(format!("unknown operator 'i {}'", operator)),
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), String> {
+        let operator = self.next()?;
+        let token = self.next()?;
+        let operand = match token.as_str() {
+            _ if self.is_register(&token) => Operand::Register(self.register(&token)?),
+            "key" | "delay" | "random" => Operand::None,
+            _ => Operand::Byte(self.byte(&token)?),
+        };
+        let instruction = match (operator.as_str(), operand) {
+            (":=", Operand::Register(y)) => [0x80 | x, y << 4],
+            (":=", Operand::Byte(n)) => [0x60 | x, n],
+            (":=", Operand::None) => match token.as_str() {
+                "key" => [0xF0 | x, 0x0A],
+                "delay" => [0xF0 | x, 0x07],
+                _ => {
+                    let token = self.next()?;
+                    [0xC0 | x, self.byte(&token)?]
+                }
+            },
+            ("+=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x4],
+            ("+=", Operand::Byte(n)) => [0x70 | x, n],
+            ("-=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x5],
+            ("-=", Operand::Byte(n)) => [0x70 | x, n.wrapping_neg()],
+            ("|=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x1],
+            ("&=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x2],
+            ("^=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x3],
+            (">>=", Operand::Register(y)) => [0x80 | x, y << 4 | 0x6],
+            ("=-", Operand::Register(y)) => [0x80 | x, y << 4 | 0x7],
+            ("<<=", Operand::Register(y)) => [0x80 | x, y << 4 | 0xE],
+            _ => return Err(format!("cannot assemble 'v{:X} {} {}'", x, operator, token)),
+        };
+        self.emit(&instruction);
+        Ok(())
+    }
+
+    fn if_statement(&mut self) -> Result<(), String> {
+        //  The condition is emitted once it is known whether it guards a
+        //  single statement or a block
+        let start = self.position;
+        self.skip_condition()?;
+        match self.next()?.as_str() {
+            "then" => {
+                let end = self.position;
+                self.position = start;
+                self.condition(false)?;
+                self.position = end;
+            }
+            "begin" => {
+                let end = self.position;
+                self.position = start;
+                self.condition(true)?;
+                self.position = end;
+                self.branches.push(self.here);
+                self.emit(&[0x10, 0x00]);
+            }
+            token => return Err(format!("expected 'then' or 'begin', found '{}'", token)),
+        }
+        Ok(())
+    }
+
+    fn skip_condition(&mut self) -> Result<(), String> {
+        self.next()?;
+        match self.next()?.as_str() {
+            "key" | "-key" => {}
+            _ => {
+                self.next()?;
+            }
+        }
+        Ok(())
+    }
+
+    //  Emits what skips the next instruction unless the condition holds,
+    //  or, `negated`, unless it does not
+    fn condition(&mut self, negated: bool) -> Result<(), String> {
+        let token = self.next()?;
+        let x = self.register(&token)?;
+        let mut comparison = self.next()?;
+        if negated {
+            comparison = match comparison.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "<" => ">=",
+                ">=" => "<",
+                ">" => "<=",
+                "<=" => ">",
+                "key" => "-key",
+                "-key" => "key",
+                other => return Err(format!("unknown comparison '{}'", other)),
+            }.to_string();
+        }
+        if comparison == "key" || comparison == "-key" {
+            self.emit(&[0xE0 | x, if comparison == "key" { 0xA1 } else { 0x9E }]);
+            return Ok(());
+        }
+        let token = self.next()?;
+        let operand = match self.is_register(&token) {
+            true => Operand::Register(self.register(&token)?),
+            false => Operand::Byte(self.byte(&token)?),
+        };
+        match (comparison.as_str(), operand) {
+            ("==", Operand::Register(y)) => self.emit(&[0x90 | x, y << 4]),
+            ("==", Operand::Byte(n)) => self.emit(&[0x40 | x, n]),
+            ("!=", Operand::Register(y)) => self.emit(&[0x50 | x, y << 4]),
+            ("!=", Operand::Byte(n)) => self.emit(&[0x30 | x, n]),
+            (">" | "<" | ">=" | "<=", _) => {
+                //  VF := operand, then subtract to get the flag
+                match operand {
+                    Operand::Register(y) => self.emit(&[0x80 | COMPARE_TEMP, y << 4]),
+                    Operand::Byte(n) => self.emit(&[0x60 | COMPARE_TEMP, n]),
+                    Operand::None => unreachable!(),
+                }
+                let (subtract, skip) = match comparison.as_str() {
+                    ">" => (0x5, 0x30),
+                    "<" => (0x7, 0x30),
+                    ">=" => (0x7, 0x40),
+                    _ => (0x5, 0x40),
+                };
+                self.emit(&[0x80 | COMPARE_TEMP, x << 4 | subtract]);
+                self.emit(&[skip | COMPARE_TEMP, 1]);
+            }
+            _ => return Err(format!("unknown comparison '{}'", comparison)),
+        }
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) {
+            return Err(format!("the label '{}' is defined twice", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.next()?;
+        let mut parameters = Vec::new();
+        loop {
+            match self.next()? {
+                token if token == "{" => break,
+                token => parameters.push(token),
+            }
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        while depth > 0 {
+            let token = self.next_token().ok_or("unexpected end of the program")?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth > 0 {
+                body.push(token);
+            }
+        }
+        self.macros.insert(name, Macro { parameters, body });
+        Ok(())
+    }
+
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(format!("the macro '{}' does not stop expanding", name));
+        }
+        let count = self.macros[name].parameters.len();
+        let mut arguments = HashMap::new();
+        for index in 0..count {
+            let argument = self.next()?;
+            arguments.insert(self.macros[name].parameters[index].clone(), argument);
+        }
+        let line = self.line;
+        let body: Vec<Token> = self.macros[name].body.iter()
+            .map(|token| Token {
+                text: arguments.get(&token.text).cloned().unwrap_or_else(|| token.text.clone()),
+                line,
+            })
+            .collect();
+        self.tokens.splice(self.position..self.position, body);
+        Ok(())
+    }
+
+    //  Evaluates `{ expression }`. Like Octo, operators all have the same
+    //  precedence and are applied from right to left.
+    fn calc(&mut self) -> Result<f64, String> {
+        self.expect("{")?;
+        let value = self.expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let left = self.term()?;
+        let operator = match self.peek() {
+            Some(operator @ ("+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "<<" | ">>" | "pow" | "min" | "max"
+                | "<" | "<=" | "==" | "!=" | ">=" | ">")) => operator.to_string(),
+            _ => return Ok(left),
+        };
+        self.next()?;
+        let right = self.expression()?;
+        let truth = |condition: bool| if condition { 1.0 } else { 0.0 };
+        Ok(match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (left as i64 & right as i64) as f64,
+            "|" => (left as i64 | right as i64) as f64,
+            "^" => (left as i64 ^ right as i64) as f64,
+            "<<" => ((left as i64) << (right as i64)) as f64,
+            ">>" => (left as i64 >> right as i64) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => truth(left < right),
+            "<=" => truth(left <= right),
+            "==" => truth(left == right),
+            "!=" => truth(left != right),
+            ">=" => truth(left >= right),
+            _ => truth(left > right),
+        })
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        Ok(match token.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                value
+            }
+            "-" => -self.term()?,
+            "~" => !(self.term()? as i64) as f64,
+            "!" => if self.term()? == 0.0 { 1.0 } else { 0.0 },
+            "sin" => self.term()?.sin(),
+            "cos" => self.term()?.cos(),
+            "tan" => self.term()?.tan(),
+            "exp" => self.term()?.exp(),
+            "log" => self.term()?.ln(),
+            "abs" => self.term()?.abs(),
+            "sqrt" => self.term()?.sqrt(),
+            "sign" => self.term()?.signum(),
+            "ceil" => self.term()?.ceil(),
+            "floor" => self.term()?.floor(),
+            "HERE" => self.here as f64,
+            "PI" => std::f64::consts::PI,
+            "E" => std::f64::consts::E,
+            _ => self.value(&token)?,
+        })
+    }
+
+    //  A number, a constant or a label defined earlier
+    fn value(&self, token: &str) -> Result<f64, String> {
+        if let Some(value) = number(token).or_else(|| self.constants.get(token).copied()) {
+            return Ok(value);
+        }
+        match self.labels.get(token) {
+            Some(&address) => Ok(address as f64),
+            None => Err(format!("undefined name '{}'", token)),
+        }
+    }
+
+    fn to_byte(value: f64) -> Result<u8, String> {
+        match value as i64 {
+            byte @ -128..=255 => Ok(byte as u8),
+            _ => Err(format!("{} does not fit in a byte", value)),
+        }
+    }
+
+    fn byte(&self, token: &str) -> Result<u8, String> {
+        Self::to_byte(self.value(token)?)
+    }
+
+    fn is_register(&self, token: &str) -> bool {
+        register(token).is_some() || self.aliases.contains_key(token)
+    }
+
+    fn register(&self, token: &str) -> Result<u8, String> {
+        register(token)
+            .or_else(|| self.aliases.get(token).copied())
+            .ok_or_else(|| format!("expected a register, found '{}'", token))
+    }
+
+    //  Emits `bytes` with the address `token` names in it, now or once
+    //  the label is defined
+    fn emit_address(&mut self, token: &str, bytes: &[u8; 2], patch: Patch) -> Result<(), String> {
+        let address = self.here;
+        self.emit(bytes);
+        match number(token).or_else(|| self.constants.get(token).copied()) {
+            Some(value) => self.patch(address, value as usize, patch),
+            None => match self.labels.get(token) {
+                Some(&value) => self.patch(address, value, patch),
+                None => {
+                    self.fixups.push(Fixup { address, name: token.to_string(), patch, line: self.line });
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    fn patch(&mut self, address: usize, value: usize, patch: Patch) -> Result<(), String> {
+        let limit = match patch {
+            Patch::Long => 0xFFFF,
+            _ => 0xFFF,
+        };
+        if value > limit {
+            return Err(format!("the address {:X} is out of reach", value));
+        }
+        let index = address - START;
+        match patch {
+            Patch::Address => {
+                self.rom[index] |= (value >> 8) as u8;
+                self.rom[index + 1] = value as u8;
+            }
+            Patch::Long => {
+                self.rom[index] = (value >> 8) as u8;
+                self.rom[index + 1] = value as u8;
+            }
+            Patch::HighNibble => self.rom[index + 1] |= (value >> 8) as u8,
+            Patch::LowByte => self.rom[index + 1] = value as u8,
+        }
+        Ok(())
+    }
+
+    //  Points the placeholder jump at `address` to the current address
+    fn jump_here(&mut self, address: usize) {
+        let target = self.here;
+        self.write(address, &[0x10 | (target >> 8) as u8 & 0x0F, target as u8]);
+    }
+
+    fn write(&mut self, address: usize, bytes: &[u8]) {
+        let index = address - START;
+        if self.rom.len() < index + bytes.len() {
+            self.rom.resize(index + bytes.len(), 0);
+        }
+        self.rom[index..index + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.write(self.here, bytes);
+        self.here += bytes.len();
+    }
+}
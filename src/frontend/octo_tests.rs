@@ -0,0 +1,127 @@
+#[cfg(test)]
+use crate::cpu::cpu::CPU;
+#[cfg(test)]
+use crate::frontend::octo::assemble;
+
+#[test]
+fn instructions_assemble_to_their_opcodes(){
+    let source = "
+        : main
+          clear
+          v0 := 5
+          i := 0x22A
+          sprite v0 v1 3
+          v1 += 2
+          v2 -= 1
+          v3 := random 0x0F
+          delay := v0
+          v4 := key
+          v5 <<= v6
+          i += v7
+          save v8
+          return
+    ";
+    assert_eq!(assemble(source), Ok(vec![
+        0x00, 0xE0, 0x60, 0x05, 0xA2, 0x2A, 0xD0, 0x13, 0x71, 0x02, 0x72, 0xFF, 0xC3, 0x0F,
+        0xF0, 0x15, 0xF4, 0x0A, 0x85, 0x6E, 0xF7, 0x1E, 0xF8, 0x55, 0x00, 0xEE,
+    ]));
+}
+
+#[test]
+fn programs_not_starting_with_main_jump_to_it(){
+    let source = "
+        : ball
+          0x80 0x80
+        : main
+          i := ball
+          jump done
+        : done
+          jump done
+    ";
+    assert_eq!(assemble(source), Ok(vec![
+        0x12, 0x04, 0x80, 0x80, 0xA2, 0x02, 0x12, 0x08, 0x12, 0x08,
+    ]));
+}
+
+#[test]
+fn control_flow_becomes_skips_and_jumps(){
+    let source = "
+        : main
+          if v0 == 3 then v1 := 1
+          if v0 != v2 then v1 := 2
+          if v0 key then clear
+          if v0 -key then clear
+          if v0 == 1 begin
+            v1 := 3
+          else
+            v1 := 4
+          end
+          loop
+            v0 += 1
+            while v0 != 10
+          again
+    ";
+    assert_eq!(assemble(source), Ok(vec![
+        0x40, 0x03, 0x61, 0x01, 0x50, 0x20, 0x61, 0x02, 0xE0, 0xA1, 0x00, 0xE0, 0xE0, 0x9E, 0x00, 0xE0,
+        0x30, 0x01, 0x12, 0x18, 0x61, 0x03, 0x12, 0x1A, 0x61, 0x04,
+        0x70, 0x01, 0x40, 0x0A, 0x12, 0x22, 0x12, 0x1A,
+    ]));
+}
+
+#[test]
+fn comparisons_run_like_in_octo(){
+    let source = "
+        : main
+          v0 := 7
+          v1 := 9
+          if v0 < v1 then v2 := 1
+          if v0 > v1 then v3 := 1
+          if v0 <= 7 then v4 := 1
+          if v1 >= 10 then v5 := 1
+          if v0 > 6 begin v6 := 1 else v6 := 2 end
+          loop
+            v7 += 1
+            while v7 < 5
+          again
+          0x00 0x00
+    ";
+    let mut cpu = CPU::new_with_memory(assemble(source).unwrap());
+    cpu.run_headless().unwrap();
+    assert_eq!(cpu.registers()[2..8], [1, 0, 1, 0, 1, 5]);
+}
+
+#[test]
+fn directives_name_values_registers_and_code(){
+    let source = "
+        :alias x v3
+        :const speed 4
+        #  Octo applies operators from right to left: 4 * (2 + 1)
+        :calc triple { speed * 2 + 1 }
+        :macro twice reg { reg += speed reg += speed }
+        : main
+          x := triple
+          twice x
+          :unpack 0xA data
+          i := long data
+          jump0 data
+        :next patched
+          v0 := 0
+        : data
+          :byte { data - main }
+          i := patched
+    ";
+    assert_eq!(assemble(source), Ok(vec![
+        0x63, 0x0C, 0x73, 0x04, 0x73, 0x04, 0x60, 0xA2, 0x61, 0x12, 0xF0, 0x00, 0x02, 0x12,
+        0xB2, 0x12, 0x60, 0x00, 0x12, 0xA2, 0x11,
+    ]));
+}
+
+#[test]
+fn mistakes_are_reported_with_their_line(){
+    assert_eq!(assemble(": main\n  jump nowhere\n"), Err("line 2: undefined name 'nowhere'".to_string()));
+    assert_eq!(assemble(": main\n  v0 := 300\n"), Err("line 2: 300 does not fit in a byte".to_string()));
+    assert_eq!(assemble(": main\n  v0 |= 3\n"), Err("line 2: cannot assemble 'v0 |= 3'".to_string()));
+    assert!(assemble("v0 := 1").unwrap_err().contains("no 'main' label"));
+    assert!(assemble(": main\n  loop\n    v0 += 1\n").unwrap_err().contains("'loop' without 'again'"));
+    assert!(assemble(": main\n: main\n").unwrap_err().contains("defined twice"));
+}
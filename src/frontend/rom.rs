@@ -0,0 +1,231 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use serde::Deserialize;
+use zip::{CompressionMethod, ZipArchive};
+use crate::cpu::quirks::Platform;
+use crate::frontend::octo;
+
+//  What fits in memory between 0x200 and its end
+const MAX_ROM_SIZE: usize = 0x1000 - 0x200;
+
+//  A program ready to load at 0x200, with what its file says about how to
+//  run it. The ROM database and the command line still take precedence.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rom {
+    pub bytes: Vec<u8>,
+    pub platform: Option<Platform>,
+    //  Quirks in the chip-8-database naming, like --quirk
+    pub quirks: Vec<(String, bool)>,
+    pub tickrate: Option<usize>,
+}
+
+impl Rom {
+    fn raw(bytes: Vec<u8>, platform: Option<Platform>) -> Rom {
+        Rom { bytes, platform, ..Rom::default() }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RomFormat {
+    //  .ch8 and .c8, and anything not recognised
+    Chip8,
+    //  .sc8
+    SuperChip,
+    //  .xo8
+    XoChip,
+    //  .gif: an Octo cartridge, whose Octo source is assembled
+    OctoCartridge,
+    //  .hex and .txt: the bytes written out in hexadecimal
+    HexText,
+    //  .zip: an archive holding a single ROM
+    Zip,
+}
+
+impl RomFormat {
+    //  By the extension of `name`, then by the first bytes of the file for
+    //  archives and cartridges that were renamed
+    pub fn detect(name: &str, bytes: &[u8]) -> RomFormat {
+        let extension = Path::new(name).extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("sc8") => RomFormat::SuperChip,
+            Some("xo8") => RomFormat::XoChip,
+            Some("gif") => RomFormat::OctoCartridge,
+            Some("hex") | Some("txt") => RomFormat::HexText,
+            Some("zip") => RomFormat::Zip,
+            _ if bytes.starts_with(b"PK\x03\x04") => RomFormat::Zip,
+            _ if bytes.starts_with(b"GIF8") => RomFormat::OctoCartridge,
+            _ => RomFormat::Chip8,
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Rom, String> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    parse(&name, bytes).map_err(|e| format!("{} ({})", e, path.display()))
+}
+
+//  Reads the contents of a file called `name`
+pub fn parse(name: &str, bytes: Vec<u8>) -> Result<Rom, String> {
+    match RomFormat::detect(name, &bytes) {
+        RomFormat::Chip8 => Ok(Rom::raw(bytes, None)),
+        RomFormat::SuperChip => Ok(Rom::raw(bytes, Some(Platform::SuperChip))),
+        RomFormat::XoChip => Ok(Rom::raw(bytes, Some(Platform::XoChip))),
+        RomFormat::OctoCartridge => parse_cartridge(&bytes),
+        RomFormat::HexText => {
+            let text = String::from_utf8(bytes).map_err(|_| "hex dump is not text".to_string())?;
+            Ok(Rom::raw(parse_hex(&text)?, None))
+        }
+        RomFormat::Zip => parse_zip(bytes),
+    }
+}
+
+//  Whitespace separated hexadecimal bytes, or runs of them like "A22A";
+//  an "0x" prefix, a leading "0200:" address and comments starting with
+//  '#' or ';' are allowed
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split(['#', ';']).next().unwrap();
+        //  Only a single hexadecimal number may come before the colon
+        let line = match line.trim_start().split_once(':') {
+            Some((address, rest)) if is_hex(address.strip_prefix("0x").unwrap_or(address)) => rest,
+            _ => line,
+        };
+        for word in line.split_whitespace() {
+            let hex = word.strip_prefix("0x").unwrap_or(word);
+            if hex.len() % 2 != 0 || !is_hex(hex) {
+                return Err(format!("invalid byte '{}' on line {}", word, number + 1));
+            }
+            for pair in hex.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).unwrap();
+                bytes.push(u8::from_str_radix(pair, 16).unwrap());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn is_hex(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_zip(bytes: Vec<u8>) -> Result<Rom, String> {
+    let invalid = |e: zip::result::ZipError| format!("invalid ZIP archive: {}", e);
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+    let files: Vec<usize> = (0..archive.len())
+        .filter(|&index| archive.by_index_raw(index).is_ok_and(|file| file.is_file()))
+        .collect();
+    let index = match files.as_slice() {
+        [index] => *index,
+        [] => return Err("the ZIP archive is empty".to_string()),
+        _ => return Err(format!("the ZIP archive holds {} files instead of a single ROM", files.len())),
+    };
+    let mut file = archive.by_index_raw(index).map_err(invalid)?;
+    let (name, method, size) = (file.name().to_string(), file.compression(), file.size() as usize);
+    //  Checked before inflating, since the size comes from the archive
+    if size > MAX_ROM_SIZE {
+        return Err(format!("{} is {} bytes, more than the {} that fit in memory", name, size, MAX_ROM_SIZE));
+    }
+    let checksum = file.crc32();
+    let mut stored = Vec::new();
+    file.read_to_end(&mut stored).map_err(|e| format!("cannot read {} from the ZIP archive: {}", name, e))?;
+    let contents = if method == CompressionMethod::STORE {
+        stored
+    } else if method == CompressionMethod::DEFLATE {
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&stored, size)
+            .map_err(|e| format!("cannot inflate {} from the ZIP archive: {}", name, e))?
+    } else {
+        return Err(format!("{} is compressed with {}, which is not supported", name, method));
+    };
+    if contents.len() != size {
+        return Err(format!("{} is truncated in the ZIP archive", name));
+    }
+    if crc32fast::hash(&contents) != checksum {
+        return Err(format!("{} is corrupted in the ZIP archive", name));
+    }
+    if RomFormat::detect(&name, &contents) == RomFormat::Zip {
+        return Err(format!("{} is another ZIP archive", name));
+    }
+    parse(&name, contents)
+}
+
+//  The JSON an Octo cartridge carries
+#[derive(Debug, Deserialize)]
+struct Cartridge {
+    program: String,
+    #[serde(default)]
+    options: CartridgeOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CartridgeOptions {
+    tickrate: Option<usize>,
+    max_size: Option<usize>,
+    shift_quirks: Option<bool>,
+    load_store_quirks: Option<bool>,
+    clip_quirks: Option<bool>,
+    jump_quirks: Option<bool>,
+    logic_quirks: Option<bool>,
+    v_blank_quirks: Option<bool>,
+}
+
+impl CartridgeOptions {
+    //  Octo tells the platforms apart by how much memory programs may use
+    fn platform(&self) -> Option<Platform> {
+        match self.max_size? {
+            3215 => Some(Platform::OriginalChip8),
+            3583 => Some(Platform::SuperChip),
+            65024 => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    fn quirks(&self) -> Vec<(String, bool)> {
+        [
+            ("shift", self.shift_quirks),
+            ("memoryLeaveIUnchanged", self.load_store_quirks),
+            //  Octo clips when asked to, this interpreter wraps when asked to
+            ("wrap", self.clip_quirks.map(|clip| !clip)),
+            ("jump", self.jump_quirks),
+            ("logic", self.logic_quirks),
+            ("vblank", self.v_blank_quirks),
+        ].iter()
+            .filter_map(|&(name, enabled)| enabled.map(|enabled| (name.to_string(), enabled)))
+            .collect()
+    }
+}
+
+//  An Octo cartridge is a GIF whose palette indices hide a payload, four
+//  bits in the low bits of each pixel's index, high half first, across all
+//  frames. The payload is a 32-bit big-endian length and that many bytes of
+//  JSON with the program's source and Octo's options.
+fn parse_cartridge(bytes: &[u8]) -> Result<Rom, String> {
+    let invalid = |e: gif::DecodingError| format!("invalid GIF: {}", e);
+    let mut reader = gif::Decoder::new(bytes).read_info().map_err(invalid)?;
+    let mut nibbles = Vec::new();
+    while let Some(frame) = reader.read_next_frame().map_err(invalid)? {
+        nibbles.extend(frame.buffer.iter().map(|index| index & 0x0F));
+    }
+    let payload: Vec<u8> = nibbles.chunks_exact(2).map(|pair| pair[0] << 4 | pair[1]).collect();
+    let (length, json) = match payload.split_first_chunk::<4>() {
+        Some((length, rest)) if u32::from_be_bytes(*length) as usize <= rest.len() =>
+            (u32::from_be_bytes(*length) as usize, rest),
+        _ => return Err("not an Octo cartridge".to_string()),
+    };
+    let cartridge: Cartridge = serde_json::from_slice(&json[..length])
+        .map_err(|e| format!("invalid Octo cartridge: {}", e))?;
+    let program = octo::assemble(&cartridge.program)
+        .map_err(|e| format!("cannot assemble the Octo cartridge: {}", e))?;
+    Ok(Rom {
+        bytes: program,
+        platform: cartridge.options.platform(),
+        quirks: cartridge.options.quirks(),
+        tickrate: cartridge.options.tickrate,
+    })
+}
@@ -0,0 +1,236 @@
+#[cfg(test)]
+use std::io::{Cursor, Write};
+#[cfg(test)]
+use crate::cpu::quirks::Platform;
+#[cfg(test)]
+use crate::frontend::rom::{self, Rom, RomFormat};
+
+#[cfg(test)]
+const PROGRAM: [u8; 4] = [0xA2, 0x2A, 0x60, 0x0C];
+
+//  An archive with `files` stored uncompressed
+#[cfg(test)]
+fn stored_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, contents) in files {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(contents).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+//  An archive with a single deflated file, written out by hand since the
+//  zip crate is built without a compressor
+#[cfg(test)]
+fn deflated_zip(name: &str, contents: &[u8]) -> Vec<u8> {
+    let deflated = miniz_oxide::deflate::compress_to_vec(contents, 6);
+    let sizes = [(deflated.len() as u32).to_le_bytes(), (contents.len() as u32).to_le_bytes()].concat();
+    let name_length = (name.len() as u16).to_le_bytes();
+    let checksum = crc32fast::hash(contents).to_le_bytes();
+    //  Version, flags, method 8, time and date
+    let common = [&[20, 0, 0, 0, 8, 0, 0, 0, 0, 0][..], &checksum, &sizes, &name_length, &[0, 0]].concat();
+
+    let mut archive = [&b"PK\x03\x04"[..], &common, name.as_bytes(), &deflated].concat();
+    let directory_start = archive.len() as u32;
+    let directory = [&b"PK\x01\x02"[..], &[20, 0], &common, &[0; 12], &[0; 4], name.as_bytes()].concat();
+    let directory_size = directory.len() as u32;
+    archive.extend(directory);
+    archive.extend([&b"PK\x05\x06"[..], &[0; 4], &[1, 0, 1, 0], &directory_size.to_le_bytes(),
+                    &directory_start.to_le_bytes(), &[0, 0]].concat());
+    archive
+}
+
+//  A GIF hiding `json` the way Octo cartridges do
+#[cfg(test)]
+fn cartridge(json: &str) -> Vec<u8> {
+    let payload = [&(json.len() as u32).to_be_bytes()[..], json.as_bytes()].concat();
+    let mut pixels: Vec<u8> = payload.iter().flat_map(|byte| [byte >> 4, byte & 0x0F]).collect();
+    let (width, height) = (64, pixels.len() / 64 + 1);
+    pixels.resize(width * height, 0);
+    let palette: Vec<u8> = (0..16).flat_map(|i| [i * 16, i * 16, i * 16]).collect();
+
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut gif, width as u16, height as u16, &palette).unwrap();
+        let frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, &pixels, None);
+        encoder.write_frame(&frame).unwrap();
+    }
+    gif
+}
+
+#[test]
+fn formats_are_recognised_by_extension_then_by_contents(){
+    assert_eq!(RomFormat::detect("pong.ch8", &[]), RomFormat::Chip8);
+    assert_eq!(RomFormat::detect("pong.C8", &[]), RomFormat::Chip8);
+    assert_eq!(RomFormat::detect("car.SC8", &[]), RomFormat::SuperChip);
+    assert_eq!(RomFormat::detect("t8nks.xo8", &[]), RomFormat::XoChip);
+    assert_eq!(RomFormat::detect("game.gif", &[]), RomFormat::OctoCartridge);
+    assert_eq!(RomFormat::detect("dump.hex", &[]), RomFormat::HexText);
+    assert_eq!(RomFormat::detect("games.zip", &[]), RomFormat::Zip);
+    assert_eq!(RomFormat::detect("download", b"PK\x03\x04"), RomFormat::Zip);
+    assert_eq!(RomFormat::detect("download", b"GIF89a"), RomFormat::OctoCartridge);
+    assert_eq!(RomFormat::detect("download", &PROGRAM), RomFormat::Chip8);
+}
+
+#[test]
+fn extensions_pick_the_platform(){
+    assert_eq!(rom::parse("pong.ch8", PROGRAM.to_vec()), Ok(Rom { bytes: PROGRAM.to_vec(), ..Rom::default() }));
+    assert_eq!(rom::parse("car.sc8", PROGRAM.to_vec()).unwrap().platform, Some(Platform::SuperChip));
+    assert_eq!(rom::parse("t8nks.xo8", PROGRAM.to_vec()).unwrap().platform, Some(Platform::XoChip));
+}
+
+#[test]
+fn hex_dumps_allow_addresses_prefixes_and_comments(){
+    let text = "# Loads\n0200: A2 2A ; I = 22A\n0x60 0C\n\n00E0D00F\n";
+    assert_eq!(rom::parse_hex(text), Ok(vec![0xA2, 0x2A, 0x60, 0x0C, 0x00, 0xE0, 0xD0, 0x0F]));
+    assert_eq!(rom::parse("dump.hex", text.as_bytes().to_vec()).unwrap().bytes.len(), 8);
+}
+
+#[test]
+fn hex_dumps_reject_anything_else(){
+    assert_eq!(rom::parse_hex("A2 2A\nA2G"), Err("invalid byte 'A2G' on line 2".to_string()));
+    assert!(rom::parse_hex("A22").is_err());
+    assert_eq!(rom::parse_hex("A2 2A: 00 E0"), Err("invalid byte '2A:' on line 1".to_string()));
+    assert!(rom::parse_hex("0200: A2 2A: 00 E0").is_err());
+}
+
+#[test]
+fn zip_archives_hold_a_single_rom(){
+    let archive = stored_zip(&[("roms/", b""), ("roms/car.sc8", &PROGRAM)]);
+    let rom = rom::parse("car.zip", archive).unwrap();
+    assert_eq!(rom.bytes, PROGRAM);
+    assert_eq!(rom.platform, Some(Platform::SuperChip));
+
+    let archive = stored_zip(&[("a.ch8", &PROGRAM), ("b.ch8", &PROGRAM)]);
+    assert!(rom::parse("games.zip", archive).unwrap_err().contains("2 files"));
+    assert!(rom::parse("empty.zip", stored_zip(&[])).is_err());
+}
+
+#[test]
+fn deflated_roms_are_inflated(){
+    let program = PROGRAM.repeat(64);
+    assert_eq!(rom::parse("pong.zip", deflated_zip("pong.ch8", &program)).unwrap().bytes, program);
+}
+
+#[test]
+fn corrupted_roms_are_refused(){
+    let program = PROGRAM.repeat(64);
+    let mut archive = deflated_zip("pong.ch8", &program);
+    //  Both the local header and the central directory carry the checksum
+    let checksum = crc32fast::hash(&program).to_le_bytes();
+    for offset in 0..archive.len() - 4 {
+        if archive[offset..offset + 4] == checksum {
+            archive[offset] ^= 0xFF;
+        }
+    }
+    assert!(rom::parse("pong.zip", archive).unwrap_err().contains("corrupted"));
+}
+
+#[test]
+fn roms_too_large_for_memory_are_not_inflated(){
+    let program = vec![0; 0x1000];
+    assert!(rom::parse("big.zip", deflated_zip("big.ch8", &program)).unwrap_err().contains("fit in memory"));
+}
+
+#[test]
+fn archives_inside_archives_are_refused(){
+    let inner = stored_zip(&[("pong.ch8", &PROGRAM)]);
+    assert!(rom::parse("outer.zip", stored_zip(&[("inner.zip", &inner)])).is_err());
+}
+
+#[test]
+fn octo_cartridges_carry_options(){
+    let json = r#"{"program": ": main i := 0x22A v0 := 12", "options": {"tickrate": 20, "maxSize": 3583,
+                   "shiftQuirks": true, "clipQuirks": true, "vBlankQuirks": false}}"#;
+    let rom = rom::parse("game.gif", cartridge(json)).unwrap();
+    assert_eq!(rom.bytes, PROGRAM);
+    assert_eq!(rom.platform, Some(Platform::SuperChip));
+    assert_eq!(rom.tickrate, Some(20));
+    assert_eq!(rom.quirks, vec![("shift".to_string(), true), ("wrap".to_string(), false), ("vblank".to_string(), false)]);
+}
+
+//  A program written the way Octo programs are, with the options Octo saves
+#[cfg(test)]
+const BOUNCE: &str = "\
+###########################################
+#
+#  Bounce
+#
+#  A ball that bounces off the screen edges.
+#
+###########################################
+
+:alias ball-x v0
+:alias ball-y v1
+:alias dx v2
+:alias dy v3
+
+:const FRAME_TIME 2
+
+: ball
+  0x60 0xF0 0xF0 0x60
+
+: wait
+  vf := FRAME_TIME
+  delay := vf
+  loop
+    vf := delay
+    if vf != 0 then
+  again
+;
+
+: main
+  ball-x := 10
+  ball-y := 5
+  dx := 1
+  dy := 1
+  i := ball
+  loop
+    sprite ball-x ball-y 4
+    wait
+    sprite ball-x ball-y 4
+    ball-x += dx
+    ball-y += dy
+    if ball-x == 0 then dx := 1
+    if ball-x == 60 then dx := -1
+    if ball-y == 0 then dy := 1
+    if ball-y == 28 then dy := -1
+  again
+";
+
+#[test]
+fn octo_cartridges_are_assembled(){
+    let json = serde_json::json!({
+        "program": BOUNCE,
+        "options": {
+            "tickrate": 20, "fillColor": "#FFCC00", "fillColor2": "#FF6600", "blendColor": "#662200",
+            "backgroundColor": "#996600", "buzzColor": "#FFAA00", "quietColor": "#000000",
+            "shiftQuirks": false, "loadStoreQuirks": false, "vfOrderQuirks": false, "clipQuirks": false,
+            "vBlankQuirks": false, "jumpQuirks": false, "logicQuirks": false, "screenRotation": 0,
+            "maxSize": 3215, "touchInputMode": "none", "fontStyle": "octo",
+        },
+    });
+    let rom = rom::parse("bounce.gif", cartridge(&json.to_string())).unwrap();
+    assert_eq!(rom.bytes, vec![
+        0x12, 0x12, 0x60, 0xF0, 0xF0, 0x60, 0x6F, 0x02, 0xFF, 0x15, 0xFF, 0x07, 0x3F, 0x00, 0x12, 0x0A,
+        0x00, 0xEE, 0x60, 0x0A, 0x61, 0x05, 0x62, 0x01, 0x63, 0x01, 0xA2, 0x02, 0xD0, 0x14, 0x22, 0x06,
+        0xD0, 0x14, 0x80, 0x24, 0x81, 0x34, 0x40, 0x00, 0x62, 0x01, 0x40, 0x3C, 0x62, 0xFF, 0x41, 0x00,
+        0x63, 0x01, 0x41, 0x1C, 0x63, 0xFF, 0x12, 0x1C,
+    ]);
+    assert_eq!(rom.platform, Some(Platform::OriginalChip8));
+    assert_eq!(rom.tickrate, Some(20));
+}
+
+#[test]
+fn octo_cartridges_that_do_not_assemble_are_errors(){
+    let json = r#"{"program": ": main\n  v0 := 300\n", "options": {}}"#;
+    assert_eq!(rom::parse("game.gif", cartridge(json)),
+               Err("cannot assemble the Octo cartridge: line 2: 300 does not fit in a byte".to_string()));
+}
+
+#[test]
+fn other_gifs_are_not_cartridges(){
+    assert!(rom::parse("picture.gif", cartridge("")).is_err());
+    assert!(rom::parse("broken.gif", b"GIF89a".to_vec()).is_err());
+}
@@ -0,0 +1,65 @@
+use ggez::{graphics, Context, GameResult};
+use ggez::graphics::{Color, DrawParam, FilterMode, Rect};
+use crate::cpu::display::Framebuffer;
+use crate::frontend::window::{self, Palette, ScalingMode, WindowSettings};
+
+//  Presents the machine's framebuffer in the window
+pub struct Screen {
+    scaling: ScalingMode,
+    fullscreen: bool,
+    palette: Palette,
+    //  Set when the window changed and the frame must be drawn again
+    dirty: bool,
+    //  The framebuffer as RGBA, kept between frames to avoid reallocating it
+    image_bytes: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(settings: &WindowSettings) -> Screen {
+        Screen {
+            scaling: settings.scaling,
+            fullscreen: settings.fullscreen,
+            palette: settings.palette,
+            dirty: true,
+            image_bytes: Vec::new(),
+        }
+    }
+
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, framebuffer: &Framebuffer) -> GameResult {
+        let palette = self.palette;
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        framebuffer.write_rgba(palette.rgba(true), palette.rgba(false), &mut self.image_bytes);
+
+        let mut image = graphics::Image::from_rgba8(ctx, width as u16, height as u16, &self.image_bytes)?;
+        let target = window::viewport(graphics::drawable_size(ctx), (width, height), self.scaling);
+        let draw_params = DrawParam::default()
+            .dest([target.x, target.y])
+            .scale([target.w / width as f32, target.h / height as f32]);
+        image.set_filter(FilterMode::Nearest);
+        //  Whatever the image does not cover is letterbox
+        let [r, g, b] = palette.background;
+        graphics::clear(ctx, Color::from_rgb(r, g, b));
+        graphics::draw(ctx, &image, draw_params)?;
+        self.dirty = false;
+        graphics::present(ctx)
+    }
+
+    //  Stays in the current mode when the switch fails
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        graphics::set_fullscreen(ctx, window::fullscreen_type(!self.fullscreen))?;
+        self.fullscreen = !self.fullscreen;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        //  Keep one coordinate unit per pixel, so the viewport computation
+        //  works in window pixels
+        graphics::set_screen_coordinates(ctx, Rect::new(0f32, 0f32, width, height))?;
+        self.dirty = true;
+        Ok(())
+    }
+}
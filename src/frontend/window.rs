@@ -0,0 +1,144 @@
+use ggez::conf::{FullscreenType, WindowMode};
+use ggez::graphics::Rect;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalingMode {
+    //  Largest whole-number multiple of the display that fits the window
+    Integer,
+    //  Largest (possibly fractional) scale that keeps the display aspect ratio
+    AspectFit,
+    //  Fill the whole window, ignoring the aspect ratio
+    Stretch,
+}
+
+impl FromStr for ScalingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "integer" => Ok(ScalingMode::Integer),
+            "fit" => Ok(ScalingMode::AspectFit),
+            "stretch" => Ok(ScalingMode::Stretch),
+            _ => Err(format!("unknown scaling mode '{}' (expected integer, fit or stretch)", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette { background: [0, 0, 0], foreground: [255, 255, 255] }
+    }
+}
+
+impl Palette {
+    //  Accepts "#rrggbb" (the # is optional)
+    pub fn parse_color(text: &str) -> Result<[u8; 3], String> {
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        let value = match hex.len() {
+            6 => u32::from_str_radix(hex, 16).ok(),
+            _ => None,
+        }.ok_or_else(|| format!("invalid color '{}' (expected #rrggbb)", text))?;
+        Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+    }
+
+    pub fn rgba(&self, pixel: bool) -> [u8; 4] {
+        let [r, g, b] = match pixel {
+            true => self.foreground,
+            false => self.background,
+        };
+        [r, g, b, 255u8]
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    //  "background,foreground", e.g. "#000000,#ffffff"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s.split(',').collect::<Vec<&str>>();
+        if colors.len() != 2 {
+            return Err(format!("invalid palette '{}' (expected background,foreground)", s));
+        }
+        Ok(Palette {
+            background: Palette::parse_color(colors[0])?,
+            foreground: Palette::parse_color(colors[1])?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowSettings {
+    pub width: f32,
+    pub height: f32,
+    pub resizable: bool,
+    pub fullscreen: bool,
+    pub scaling: ScalingMode,
+    pub palette: Palette,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            width: 640f32,
+            height: 320f32,
+            resizable: true,
+            fullscreen: false,
+            scaling: ScalingMode::AspectFit,
+            palette: Palette::default(),
+        }
+    }
+}
+
+impl WindowSettings {
+    pub fn window_mode(&self) -> WindowMode {
+        WindowMode::default()
+            .dimensions(self.width, self.height)
+            .min_dimensions(64f32, 32f32)
+            .resizable(self.resizable)
+            .fullscreen_type(fullscreen_type(self.fullscreen))
+    }
+}
+
+pub fn fullscreen_type(fullscreen: bool) -> FullscreenType {
+    match fullscreen {
+        true => FullscreenType::Desktop,
+        false => FullscreenType::Windowed,
+    }
+}
+
+//  Computes where the display image should be drawn inside a window of the
+//  given size; whatever is left outside the returned rectangle is letterbox.
+pub fn viewport(window_size: (f32, f32), display_size: (usize, usize), mode: ScalingMode) -> Rect {
+    let (window_width, window_height) = window_size;
+    let (display_width, display_height) = (display_size.0 as f32, display_size.1 as f32);
+    let horizontal_scale = window_width / display_width;
+    let vertical_scale = window_height / display_height;
+
+    let (scale_x, scale_y) = match mode {
+        ScalingMode::Stretch => (horizontal_scale, vertical_scale),
+        ScalingMode::AspectFit => {
+            let scale = horizontal_scale.min(vertical_scale);
+            (scale, scale)
+        }
+        ScalingMode::Integer => {
+            //  A window smaller than the display still shows something,
+            //  so the scale never goes below 1
+            let scale = horizontal_scale.min(vertical_scale).floor().max(1f32);
+            (scale, scale)
+        }
+    };
+
+    let width = display_width * scale_x;
+    let height = display_height * scale_y;
+    Rect::new(((window_width - width) / 2f32).floor(),
+              ((window_height - height) / 2f32).floor(),
+              width,
+              height)
+}
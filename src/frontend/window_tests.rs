@@ -0,0 +1,31 @@
+#[cfg(test)]
+use crate::frontend::window::{viewport, ScalingMode};
+
+#[test]
+fn aspect_fit_letterboxes_wide_windows(){
+    let rect = viewport((1000f32, 320f32), (64, 32), ScalingMode::AspectFit);
+    assert_eq!((rect.w, rect.h), (640f32, 320f32));
+    assert_eq!((rect.x, rect.y), (180f32, 0f32));
+}
+
+#[test]
+fn integer_scaling_rounds_down(){
+    let rect = viewport((700f32, 500f32), (64, 32), ScalingMode::Integer);
+    assert_eq!((rect.w, rect.h), (640f32, 320f32));
+    assert_eq!((rect.x, rect.y), (30f32, 90f32));
+}
+
+#[test]
+fn integer_scaling_follows_display_resolution(){
+    //  Same window, hi-res display: the scale halves
+    let rect = viewport((700f32, 500f32), (128, 64), ScalingMode::Integer);
+    assert_eq!((rect.w, rect.h), (640f32, 320f32));
+    let rect = viewport((1280f32, 640f32), (128, 64), ScalingMode::Integer);
+    assert_eq!((rect.w, rect.h), (1280f32, 640f32));
+}
+
+#[test]
+fn stretch_fills_the_window(){
+    let rect = viewport((800f32, 800f32), (64, 32), ScalingMode::Stretch);
+    assert_eq!((rect.x, rect.y, rect.w, rect.h), (0f32, 0f32, 800f32, 800f32));
+}
@@ -0,0 +1,7 @@
+pub mod cpu;
+#[cfg(feature = "frontend")]
+pub mod frontend;
+mod machine;
+mod machine_tests;
+
+pub use machine::Machine;
@@ -0,0 +1,161 @@
+use std::io;
+use crate::cpu::coverage::Coverage;
+use crate::cpu::cpu::CPU;
+use crate::cpu::display::Framebuffer;
+use crate::cpu::error::ExecutionError;
+use crate::cpu::font::Font;
+use crate::cpu::machine_code::MachineCodePolicy;
+use crate::cpu::memory_map::MemoryMap;
+use crate::cpu::profiler::Profiler;
+use crate::cpu::stack::StackStorage;
+use crate::cpu::quirks::Quirks;
+use crate::cpu::timing::TimingMode;
+use crate::cpu::trace::{TraceRecord, Tracer};
+use crate::cpu::tracediff::{self, Divergence};
+
+//  A CHIP-8 machine for programs that embed the interpreter: load a ROM,
+//  feed it keys, run it a frame at a time and read back the display.
+#[derive(Default)]
+pub struct Machine {
+    cpu: CPU,
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine::default()
+    }
+
+    //  Resets the machine and loads `rom` at 0x200; the configuration is kept
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.cpu.load_rom(rom)
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    pub fn set_timing(&mut self, timing: TimingMode) {
+        self.cpu.set_timing(timing);
+    }
+
+    //  Instructions per 60 Hz frame when the timing mode counts instructions
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: usize) {
+        self.cpu.set_cycles_per_frame(cycles_per_frame);
+    }
+
+    //  What 0NNN calls to 1802 machine language do
+    pub fn set_machine_code_policy(&mut self, policy: MachineCodePolicy) {
+        self.cpu.set_machine_code_policy(policy);
+    }
+
+    pub fn set_stack(&mut self, depth: usize, storage: StackStorage) -> Result<(), String> {
+        self.cpu.set_stack(depth, storage)
+    }
+
+    pub fn set_font(&mut self, font: Font) {
+        self.cpu.set_font(font);
+    }
+
+    pub fn set_memory_map(&mut self, memory_map: MemoryMap) {
+        self.cpu.set_memory_map(memory_map);
+    }
+
+    //  Stops programs that write outside the program area or run into data
+    pub fn set_strict(&mut self, strict: bool) {
+        self.cpu.set_strict(strict);
+    }
+
+    //  Trades memory for speed by decoding every address only once
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cpu.set_decode_cache(enabled);
+    }
+
+    //  Runs frames a basic block at a time instead of an instruction at a time
+    pub fn set_basic_blocks(&mut self, enabled: bool) {
+        self.cpu.set_basic_blocks(enabled);
+    }
+
+    //  Executes a single instruction, or nothing once the program halted
+    pub fn step(&mut self) {
+        self.cpu.step();
+    }
+
+    //  Executes what is left of the current 60 Hz frame
+    pub fn run_frame(&mut self) {
+        self.cpu.run_frame();
+    }
+
+    //  Presses or releases one of the sixteen keys, 0x0 to 0xF
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.cpu.set_key(key & 0x0F, pressed);
+    }
+
+    //  The display, one bit per pixel
+    pub fn framebuffer(&self) -> &Framebuffer {
+        self.cpu.framebuffer()
+    }
+
+    pub fn display_size(&self) -> (usize, usize) {
+        self.cpu.display_size()
+    }
+
+    //  Whether the display changed since the last call
+    pub fn take_display_dirty(&mut self) -> bool {
+        self.cpu.take_display_dirty()
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        self.cpu.registers()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        self.cpu.memory()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.cpu.is_halted()
+    }
+
+    //  Why the machine halted, when the program failed rather than ending with 0000
+    pub fn error(&self) -> Option<ExecutionError> {
+        self.cpu.error()
+    }
+
+    //  Why tracing stopped early, if it did
+    pub fn trace_error(&self) -> Option<&io::Error> {
+        self.cpu.trace_error()
+    }
+
+    //  Records every instruction executed from now on
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.cpu.set_tracer(tracer);
+    }
+
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.cpu.set_profiler(profiler);
+    }
+
+    //  The profile so far, which stops profiling
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.cpu.take_profiler()
+    }
+
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.cpu.set_coverage(coverage);
+    }
+
+    //  The coverage so far, which stops recording it
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.cpu.take_coverage()
+    }
+
+    //  Runs the program against a reference trace, up to its first divergence
+    pub fn compare_trace(&mut self, reference: &[TraceRecord]) -> Option<Divergence> {
+        tracediff::compare(&mut self.cpu, reference)
+    }
+
+    //  The interpreter itself, for tools that inspect it
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+use crate::Machine;
+#[cfg(test)]
+use crate::cpu::coverage::Coverage;
+#[cfg(test)]
+use crate::cpu::profiler::Profiler;
+
+#[test]
+fn runs_a_rom_a_frame_at_a_time(){
+    let mut machine = Machine::new();
+    machine.set_cycles_per_frame(4);
+    machine.load_rom(&[
+        0x60, 0x05,     //  Set R0 to 5
+        0x70, 0x01,     //  Increment R0
+        0x12, 0x02,     //  Loop
+    ]).unwrap();
+    //  The jump to 0x200, the load, an increment and the loop
+    machine.run_frame();
+    assert_eq!(machine.registers()[0], 6);
+    //  Two increments and two loops
+    machine.run_frame();
+    assert_eq!(machine.registers()[0], 8);
+}
+
+#[test]
+fn draws_into_the_framebuffer(){
+    let mut machine = Machine::new();
+    machine.load_rom(&[
+        0x60, 0x00,     //  Set R0 to 0 (the font sprite for 0)
+        0xF0, 0x29,     //  Point at it
+        0xD0, 0x05,     //  Draw it at (0, 0)
+    ]).unwrap();
+    for _ in 0..4 {
+        machine.step();
+    }
+    let row: Vec<bool> = machine.framebuffer().pixels().take(5).collect();
+    assert_eq!(row, vec![true, true, true, true, false]);
+    assert!(machine.framebuffer().pixel(0, 1));
+    assert!(!machine.framebuffer().pixel(1, 1));
+    assert!(machine.take_display_dirty());
+    assert!(!machine.take_display_dirty());
+}
+
+#[test]
+fn a_halted_machine_stays_halted(){
+    let mut machine = Machine::new();
+    machine.load_rom(&[0x00, 0x00, 0x60, 0x05]).unwrap();
+    for _ in 0..4 {
+        machine.step();
+    }
+    assert!(machine.is_halted());
+    assert_eq!(machine.registers()[0], 0);
+}
+
+#[test]
+fn load_rom_resets_the_machine(){
+    let mut machine = Machine::new();
+    machine.load_rom(&[0x60, 0x05, 0x00, 0x00]).unwrap();
+    while !machine.is_halted() {
+        machine.step();
+    }
+    machine.load_rom(&[0xF0, 0x0A]).unwrap();
+    assert!(!machine.is_halted());
+    assert_eq!(machine.registers()[0], 0);
+    machine.set_key(0x3, true);
+    machine.step();
+    machine.step();
+    machine.set_key(0x3, false);
+    machine.step();
+    assert_eq!(machine.registers()[0], 3);
+    assert!(machine.load_rom(&vec![0; 0xE01]).is_err());
+}
+
+#[test]
+fn profiles_and_coverage_are_collected_through_the_machine(){
+    let rom = [0x60, 0x05, 0x00, 0x00];
+    let mut machine = Machine::new();
+    machine.load_rom(&rom).unwrap();
+    machine.set_profiler(Profiler::new());
+    machine.set_coverage(Coverage::new(rom.len()));
+    while !machine.is_halted() {
+        machine.step();
+    }
+    //  The jump to 0x200, the load and the halt
+    assert_eq!(machine.take_profiler().unwrap().instructions(), 3);
+    assert_eq!(machine.take_coverage().unwrap().rom_bytes_used(), 4);
+    assert!(machine.take_profiler().is_none());
+}
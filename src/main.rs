@@ -1,8 +1,238 @@
-mod cpu;
-use cpu::cpu::CPU;
+use chip_8::Machine;
+use chip_8::cpu::coverage::Coverage;
+use chip_8::cpu::font::{Font, FontSet};
+use chip_8::cpu::memory_map::MemoryMap;
+use chip_8::cpu::profiler::Profiler;
+use chip_8::cpu::quirks::{Platform, Quirks};
+use chip_8::cpu::stack::{self, StackStorage};
+use chip_8::cpu::timing::TimingMode;
+use chip_8::cpu::trace::{self, Tracer};
+use chip_8::cpu::tracediff::Divergence;
+use chip_8::frontend::{self, FrontendSettings};
+use chip_8::frontend::cli::{Command, FontChoice, Options};
+use chip_8::frontend::config::{self, Config};
+use chip_8::frontend::database::{self, Database, RomEntry};
+use chip_8::frontend::gamepad::GamepadMap;
+use chip_8::frontend::keymap::KeyMap;
+use chip_8::frontend::rom::{self, Rom};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 
 fn main() {
-    let mut cpu = CPU::new_with_memory(vec![
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => exit_with_error(&message),
+    };
+
+    let config_path = options.config_path.clone()
+        .or_else(|| existing_file(config::DEFAULT_CONFIG_FILE));
+    let config = match config_path {
+        Some(path) => Config::load(&path).unwrap_or_else(|message| exit_with_error(&message)),
+        None => Config::default(),
+    };
+    let mut database = Database::bundled();
+    let database_path = options.database_path.clone()
+        .or_else(|| existing_file(database::DEFAULT_DATABASE_FILE));
+    if let Some(path) = database_path {
+        database.extend_with(Database::load(&path).unwrap_or_else(|message| exit_with_error(&message)));
+    }
+
+    let rom = match &options.rom_path {
+        Some(path) => rom::load(path).unwrap_or_else(|message| exit_with_error(&message)),
+        None => Rom { bytes: demo_program(), ..Rom::default() },
+    };
+    let entry = match database.lookup(&database::rom_hash(&rom.bytes)) {
+        Some((title, entry)) => {
+            println!("Found {} in the ROM database", title);
+            Some(entry)
+        }
+        None => None,
+    };
+
+    let (quirks, tickrate) = machine_settings(&options, entry, &rom)
+        .unwrap_or_else(|message| exit_with_error(&message));
+    let font = font(&options, entry, &rom).unwrap_or_else(|message| exit_with_error(&message));
+    let settings = frontend_settings(&options, &config, entry)
+        .unwrap_or_else(|message| exit_with_error(&message));
+    if options.print_keymap {
+        println!("Keyboard:\n{}\nGamepad:\n{}", settings.keymap, settings.gamepad);
+    }
+
+    let mut machine = Machine::new();
+    machine.set_quirks(quirks);
+    if let Some(tickrate) = tickrate {
+        machine.set_cycles_per_frame(tickrate);
+    }
+    if let Some(timing) = options.timing {
+        machine.set_timing(timing);
+    }
+    if options.timing == Some(TimingMode::CosmacVip) {
+        machine.set_memory_map(MemoryMap::cosmac_vip());
+    }
+    machine.set_strict(options.strict);
+    machine.set_font(font);
+    let stack_storage = match options.vip_stack {
+        true => StackStorage::Memory { top: stack::VIP_STACK_TOP },
+        false => StackStorage::Internal,
+    };
+    machine.set_stack(stack_depth(&options, entry, &rom), stack_storage)
+        .unwrap_or_else(|message| exit_with_error(&message));
+    if let Some(policy) = options.machine_code {
+        machine.set_machine_code_policy(policy);
+    }
+    machine.set_decode_cache(options.decode_cache);
+    machine.set_basic_blocks(options.basic_blocks);
+    machine.load_rom(&rom.bytes).unwrap_or_else(|message| exit_with_error(&message));
+
+    if let Some(path) = &options.trace_path {
+        let file = File::create(path)
+            .unwrap_or_else(|e| exit_with_error(&format!("cannot create {}: {}", path.display(), e)));
+        machine.set_tracer(Tracer::new(Box::new(BufWriter::new(file)), options.trace_format()));
+    }
+    if options.profile_path.is_some() {
+        machine.set_profiler(Profiler::new());
+    }
+    if options.coverage_path.is_some() {
+        machine.set_coverage(Coverage::new(rom.bytes.len()));
+    }
+
+    let divergence = match options.command {
+        Command::Run => {
+            frontend::run(&mut machine, &settings);
+            None
+        }
+        Command::TraceDiff => trace_diff(&mut machine, &options),
+    };
+
+    if let Some(e) = machine.trace_error() {
+        eprintln!("trace stopped: {}", e);
+    }
+    if let (Some(path), Some(profiler)) = (&options.profile_path, machine.take_profiler()) {
+        write_report(path, &profiler.to_string());
+    }
+    if let (Some(path), Some(coverage)) = (&options.coverage_path, machine.take_coverage()) {
+        let report = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html") | Some("htm") => coverage.html_map(machine.memory()),
+            _ => coverage.annotated_disassembly(machine.memory()),
+        };
+        write_report(path, &report);
+    }
+    if let Some(divergence) = divergence {
+        exit_with_error(&divergence.to_string());
+    }
+    if let Some(error) = machine.error() {
+        exit_with_error(&error.to_string());
+    }
+}
+
+//  Reports go to a file, or to stdout when the path is "-"
+fn write_report(path: &Path, report: &str) {
+    if path == Path::new("-") {
+        print!("{}", report);
+    } else if let Err(e) = std::fs::write(path, report) {
+        exit_with_error(&format!("cannot write {}: {}", path.display(), e));
+    }
+}
+
+//  The first divergence from the reference trace, reported once the
+//  profile and coverage of the run are written
+fn trace_diff(machine: &mut Machine, options: &Options) -> Option<Divergence> {
+    let path = options.reference_trace.as_ref().expect("tracediff needs a reference trace");
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| exit_with_error(&format!("cannot read {}: {}", path.display(), e)));
+    let reference = trace::read_trace(&text, options.reference_trace_format())
+        .unwrap_or_else(|message| exit_with_error(&format!("{} ({})", message, path.display())));
+    let divergence = machine.compare_trace(&reference);
+    if divergence.is_none() {
+        println!("Traces match for all {} steps", reference.len());
+    }
+    divergence
+}
+
+//  The platform from the command line, or else from the ROM database, or
+//  else from the ROM's file format
+fn platform(options: &Options, entry: Option<&RomEntry>, rom: &Rom) -> Option<Platform> {
+    options.platform.or_else(|| entry.and_then(RomEntry::platform)).or(rom.platform)
+}
+
+//  Command line first, then the ROM database, then what the ROM file says,
+//  then the platform defaults
+fn machine_settings(options: &Options, entry: Option<&RomEntry>, rom: &Rom) -> Result<(Quirks, Option<usize>), String> {
+    let platform = platform(options, entry, rom);
+    let mut quirks = match (platform, entry) {
+        (Some(platform), Some(entry)) => entry.quirks_for(platform)?,
+        (Some(platform), None) => platform.quirks(),
+        (None, _) => Quirks::default(),
+    };
+    if entry.is_none() {
+        for (name, enabled) in &rom.quirks {
+            quirks.set(name, *enabled)?;
+        }
+    }
+    for (name, enabled) in &options.quirks {
+        quirks.set(name, *enabled)?;
+    }
+    let tickrate = options.tickrate
+        .or_else(|| entry.and_then(|entry| entry.tickrate))
+        .or(rom.tickrate)
+        .or_else(|| platform.map(|platform| platform.tickrate()));
+    Ok((quirks, tickrate))
+}
+
+//  Command line first, then the platform
+fn stack_depth(options: &Options, entry: Option<&RomEntry>, rom: &Rom) -> usize {
+    let default = platform(options, entry, rom).map_or(stack::DEFAULT_STACK_DEPTH, |platform| platform.stack_depth());
+    //  Only as deep as the VIP's stack region allows when kept there
+    let default = match options.vip_stack {
+        true => default.min(stack::VIP_STACK_DEPTH),
+        false => default,
+    };
+    options.stack_depth.unwrap_or(default)
+}
+
+//  The font from the command line, or the one of the platform
+fn font(options: &Options, entry: Option<&RomEntry>, rom: &Rom) -> Result<Font, String> {
+    let font = match &options.font {
+        Some(FontChoice::Set(set)) => set.font(),
+        Some(FontChoice::File(path)) => Font::load(path)?,
+        None => platform(options, entry, rom).map_or(FontSet::Standard, |platform| platform.font_set()).font(),
+    };
+    match options.font_address {
+        Some(address) => font.at(address),
+        None => Ok(font),
+    }
+}
+
+//  Key bindings from the ROM database are added to the default layout and
+//  can be overridden by the configuration file
+fn frontend_settings(options: &Options, config: &Config, entry: Option<&RomEntry>) -> Result<FrontendSettings, String> {
+    let rom_name = options.rom_name();
+    let (database_keys, database_buttons) = entry.map(RomEntry::key_bindings).unwrap_or_default();
+    let keymap = config.keymap_for(KeyMap::default().with_additions(&database_keys)?, rom_name.as_deref())?;
+    let gamepad = config.gamepad_map_for(GamepadMap::default().with_additions(&database_buttons)?, rom_name.as_deref())?;
+
+    let mut window = options.window.clone();
+    let database_palette = match entry {
+        Some(entry) => entry.palette()?,
+        None => None,
+    };
+    window.palette = options.palette.or(database_palette).unwrap_or_default();
+    Ok(FrontendSettings { window, keymap, gamepad })
+}
+
+fn existing_file(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    if path.exists() { Some(path) } else { None }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn demo_program() -> Vec<u8> {
+    vec![
         0x12, 0x4E,
         0x08, 0x19,
         0x01, 0x01,
@@ -60,6 +290,5 @@ fn main() {
         0x22, 0x42,
         0xD0, 0x17,
         0x12, 0x64,
-    ]);
-    cpu.run();
+    ]
 }